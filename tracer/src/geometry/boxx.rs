@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use crate::{math::vec3::{Point3, Vec3}, graphics::{material::MaterialSync, bvh::BVH}};
 
-use super::{hittable::{HittableComposite, HittableSync}, Quad};
+use super::{hittable::{HittableComposite, HittableSync, new_object_id}, Quad};
 
 
 pub fn box_new(back_bottom_left: Point3, front_upper_right: Point3, material: Arc<MaterialSync>) -> Arc<HittableSync> {
@@ -22,6 +22,8 @@ pub fn box_new(back_bottom_left: Point3, front_upper_right: Point3, material: Ar
     let width = Vec3::new(max.x() - min.x(), 0.0, 0.0);
     let height = Vec3::new(0.0, max.y() - min.y(), 0.0);
     let depth = Vec3::new(0.0, 0.0, max.z() - min.z());
+    // The faces are one object
+    let id = new_object_id();
 
     // front
     _quads.add_hittable(Arc::new(Quad::new(
@@ -29,7 +31,7 @@ pub fn box_new(back_bottom_left: Point3, front_upper_right: Point3, material: Ar
         height,
         width,
         material.clone()
-    )));
+    ).with_object_id(id)));
 
     // right
     _quads.add_hittable(Arc::new(Quad::new(
@@ -37,7 +39,7 @@ pub fn box_new(back_bottom_left: Point3, front_upper_right: Point3, material: Ar
         height,
         -depth,
         material.clone()
-    )));
+    ).with_object_id(id)));
 
     // back
     _quads.add_hittable(Arc::new(Quad::new(
@@ -45,7 +47,7 @@ pub fn box_new(back_bottom_left: Point3, front_upper_right: Point3, material: Ar
         height,
         -width,
         material.clone()
-    )));
+    ).with_object_id(id)));
 
     // left
     _quads.add_hittable(Arc::new(Quad::new(
@@ -53,7 +55,7 @@ pub fn box_new(back_bottom_left: Point3, front_upper_right: Point3, material: Ar
         height,
         depth,
         material.clone()
-    )));
+    ).with_object_id(id)));
 
     // top
     _quads.add_hittable(Arc::new(Quad::new(
@@ -61,7 +63,7 @@ pub fn box_new(back_bottom_left: Point3, front_upper_right: Point3, material: Ar
         -depth,
         width,
         material.clone()
-    )));
+    ).with_object_id(id)));
 
     // bottom
     _quads.add_hittable(Arc::new(Quad::new(
@@ -69,7 +71,7 @@ pub fn box_new(back_bottom_left: Point3, front_upper_right: Point3, material: Ar
         -depth,
         width,
         material.clone()
-    )));
+    ).with_object_id(id)));
 
     Arc::new(BVH::new(&mut _quads))
     // Arc::new(_quads)
@@ -7,7 +7,7 @@ use crate::graphics::texture::TextureSync;
 use crate::math::interval::{Interval, UNIVERSE};
 use crate::math::vec3::{Color, Vec3};
 
-use super::hittable::{HitRecord, Hittable};
+use super::hittable::{HitRecord, Hittable, ObjectId, new_object_id};
 
 
 #[derive(Clone)]
@@ -15,6 +15,7 @@ pub struct ConstantMedium {
     boundry: Arc<HittableSync>,
     neg_inv_density: f64,
     phase_function: Arc<MaterialSync>,
+    id: ObjectId
 }
 
 impl ConstantMedium {
@@ -22,7 +23,8 @@ impl ConstantMedium {
         ConstantMedium { 
             boundry: boundry, 
             neg_inv_density: -1.0 / density, 
-            phase_function: Arc::new(Isotropic::new_texture(texture))
+            phase_function: Arc::new(Isotropic::new_texture(texture)),
+            id: new_object_id()
         }
     }
 
@@ -30,7 +32,8 @@ impl ConstantMedium {
         ConstantMedium { 
             boundry: boundry, 
             neg_inv_density: -1.0 / density, 
-            phase_function: Arc::new(Isotropic::new_color(color))
+            phase_function: Arc::new(Isotropic::new_color(color)),
+            id: new_object_id()
         }
     }
 }
@@ -79,6 +82,7 @@ impl Hittable for ConstantMedium {
         );
 
         hitrec.front_face = true;
+        hitrec.object_id = self.id;
 
         Some(hitrec)
     }
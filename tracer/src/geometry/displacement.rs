@@ -0,0 +1,202 @@
+//! Displacement mapping.
+//!
+//! The base surface is tessellated into triangles, every vertex is moved along its
+//! normal by a height texture, and the triangles are put in a BVH of their own.
+//! Unlike bump and normal maps this changes the actual shape, so silhouettes and
+//! shadows follow the displaced surface.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::geometry::{
+    Triangle,
+    Vertex,
+    hittable::{HittableSync, HittableComposite, UvSurfaceSync, new_object_id}
+};
+use crate::graphics::{bvh::BVH, material::MaterialSync, texture::TextureSync};
+use crate::math::vec3::{Point3, Vec3};
+
+// Keeps a small edge length from asking for billions of triangles
+const MAX_SUBDIVISIONS: usize = 1024;
+// Vertices closer than this are the same vertex when smoothing normals
+const WELD_DISTANCE: f64 = 1e-6;
+
+
+/// How finely the base surface is cut into triangles before displacing it
+#[derive(Clone, Copy)]
+pub enum Tessellation {
+    /// Subdivisions along each edge of the surface, or of each triangle of a mesh
+    Level(usize),
+    /// Subdivide until the edges of the base surface are at most this long
+    EdgeLength(f64)
+}
+
+impl Tessellation {
+    fn subdivisions(&self, edge_length: f64) -> usize {
+        let subdivisions = match *self {
+            Tessellation::Level(level) => level,
+            Tessellation::EdgeLength(target) if target > 0.0 => (edge_length / target).ceil() as usize,
+            Tessellation::EdgeLength(_) => MAX_SUBDIVISIONS
+        };
+
+        subdivisions.clamp(1, MAX_SUBDIVISIONS)
+    }
+}
+
+pub struct Displacement {
+    height: Arc<TextureSync>,   // scalar texture, a height of 0 leaves the surface in place
+    scale: f64,                 // distance a height of 1 moves the surface, negative values push it in
+    tessellation: Tessellation
+}
+
+impl Displacement {
+    pub fn new(height: Arc<TextureSync>, scale: f64, tessellation: Tessellation) -> Self {
+        Displacement { height, scale, tessellation }
+    }
+
+    /// Displace a surface, tessellated as a grid over its texture coordinates.
+    /// Grid cells with a corner off the surface are left out.
+    pub fn displace_surface(&self, surface: &UvSurfaceSync) -> Arc<HittableSync> {
+        let (u_length, v_length) = parametric_lengths(surface);
+        let u_steps = self.tessellation.subdivisions(u_length);
+        let v_steps = self.tessellation.subdivisions(v_length);
+
+        let mut grid = Vec::with_capacity(u_steps + 1);
+        for i in 0..=u_steps {
+            let column: Vec<Option<Vertex>> = (0..=v_steps).map(|j| {
+                let (u, v) = (i as f64 / u_steps as f64, j as f64 / v_steps as f64);
+                let hitrec = surface.surface_at(u, v)?;
+
+                Some(self.displace_vertex(Vertex::new(hitrec.point, hitrec.normal, u, v)))
+            }).collect();
+            grid.push(column);
+        }
+
+        // Two triangles per cell, wound like dP/du x dP/dv so they face out
+        let mut faces = Vec::with_capacity(2 * u_steps * v_steps);
+        for i in 0..u_steps {
+            for j in 0..v_steps {
+                let corners = (grid[i][j], grid[i + 1][j], grid[i + 1][j + 1], grid[i][j + 1]);
+                if let (Some(a), Some(b), Some(c), Some(d)) = corners {
+                    faces.push([a, b, c]);
+                    faces.push([a, c, d]);
+                }
+            }
+        }
+
+        build_triangles(faces, surface.material())
+    }
+
+    /// Displace a triangle mesh, subdividing every triangle the same number of times,
+    /// so triangles sharing an edge stay joined. Shared vertices need the same normal
+    /// and texture coordinates on both sides, or the mesh tears there.
+    pub fn displace_mesh(&self, triangles: &[Triangle]) -> Arc<HittableSync> {
+        let Some(material) = triangles.first().map(|triangle| triangle.material.clone()) else {
+            return Arc::new(HittableComposite::new());
+        };
+
+        let longest_edge = triangles.iter()
+            .flat_map(|triangle| {
+                let [a, b, c] = triangle.vertices.map(|vertex| vertex.position);
+                [(b - a).length(), (c - b).length(), (a - c).length()]
+            })
+            .fold(0.0, f64::max);
+        let steps = self.tessellation.subdivisions(longest_edge);
+
+        let mut faces = Vec::with_capacity(triangles.len() * steps * steps);
+        for triangle in triangles {
+            // Vertex at i steps toward the second corner and j steps toward the third
+            let vertex = |i: usize, j: usize| {
+                let (beta, gamma) = (i as f64 / steps as f64, j as f64 / steps as f64);
+                self.displace_vertex(Vertex::interpolate(&triangle.vertices, [1.0 - beta - gamma, beta, gamma]))
+            };
+
+            for i in 0..steps {
+                for j in 0..steps - i {
+                    faces.push([vertex(i, j), vertex(i + 1, j), vertex(i, j + 1)]);
+                    if i + j + 1 < steps {
+                        faces.push([vertex(i + 1, j), vertex(i + 1, j + 1), vertex(i, j + 1)]);
+                    }
+                }
+            }
+        }
+
+        build_triangles(faces, material)
+    }
+
+    fn displace_vertex(&self, vertex: Vertex) -> Vertex {
+        let height = self.height.scalar_value(vertex.u, vertex.v, &vertex.position, &vertex.normal);
+
+        Vertex { position: vertex.position + self.scale * height * vertex.normal, ..vertex }
+    }
+}
+
+/// Approximate lengths of the surface along u and along v, the longest of a few
+/// lines walked across it
+fn parametric_lengths(surface: &UvSurfaceSync) -> (f64, f64) {
+    const LINES: usize = 8;
+    const STEPS: usize = 64;
+
+    let line_length = |point_at: &dyn Fn(f64) -> Option<Point3>| {
+        let points: Vec<Point3> = (0..=STEPS).filter_map(|step| point_at(step as f64 / STEPS as f64)).collect();
+        points.windows(2).map(|pair| (pair[1] - pair[0]).length()).sum::<f64>()
+    };
+
+    let mut lengths = (0.0_f64, 0.0_f64);
+    for line in 0..=LINES {
+        let across = line as f64 / LINES as f64;
+        let u_length = line_length(&|u| surface.surface_at(u, across).map(|hitrec| hitrec.point));
+        let v_length = line_length(&|v| surface.surface_at(across, v).map(|hitrec| hitrec.point));
+        lengths = (lengths.0.max(u_length), lengths.1.max(v_length));
+    }
+
+    lengths
+}
+
+/// Smooth the displaced normals, then turn the faces into triangles in a BVH.
+/// The triangles are one object.
+fn build_triangles(mut faces: Vec<[Vertex; 3]>, material: Arc<MaterialSync>) -> Arc<HittableSync> {
+    smooth_normals(&mut faces);
+    let id = new_object_id();
+
+    let mut triangles = HittableComposite::new();
+    for face in faces {
+        // Collapsed cells, e.g. at the poles of a sphere
+        let [a, b, c] = face.map(|vertex| vertex.position);
+        if (b - a).cross(c - a).near_zero() {
+            continue;
+        }
+        triangles.add_hittable(Arc::new(Triangle::new(face, material.clone()).with_object_id(id)));
+    }
+    if triangles.objects.is_empty() {
+        return Arc::new(triangles);
+    }
+
+    Arc::new(BVH::new(&mut triangles))
+}
+
+/// Replace the vertex normals by the area weighted normals of the faces around them.
+/// Vertices are matched by position, so normals are shared across texture seams,
+/// poles, and the triangles of a mesh.
+fn smooth_normals(faces: &mut [[Vertex; 3]]) {
+    let weld = |point: Point3| {
+        let snap = |coordinate: f64| (coordinate / WELD_DISTANCE).round() as i64;
+        (snap(point.x()), snap(point.y()), snap(point.z()))
+    };
+
+    let mut normals: HashMap<(i64, i64, i64), Vec3> = HashMap::new();
+    for [a, b, c] in faces.iter() {
+        // Twice the face area long
+        let face_normal = (b.position - a.position).cross(c.position - a.position);
+        for vertex in [a, b, c] {
+            *normals.entry(weld(vertex.position)).or_insert(Vec3::zero()) += face_normal;
+        }
+    }
+
+    for vertex in faces.iter_mut().flatten() {
+        let normal = normals[&weld(vertex.position)];
+        if !normal.near_zero() {
+            vertex.normal = normal.unit();
+        }
+    }
+}
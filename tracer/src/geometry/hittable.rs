@@ -1,19 +1,46 @@
 
 use std::vec::Vec;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rand::random;
 
 use crate::math::vec3::Color;
 use crate::math::interval::Interval;
 use crate::math::vec3::{Point3, Vec3};
+use crate::math::utils::orthonormal_basis;
 use crate::rendering::color::COLOR_BLACK;
 use crate::geometry::Ray;
-use crate::graphics::{material::Material, aabb::AABB, light::Light};
+use crate::graphics::{material::{Material, MaterialSync}, aabb::AABB, light::Light, light_bvh::EmitterSync, texture::Footprint};
+
+
+/// Tells objects apart. Parts of one object, like the faces of a box or the
+/// triangles of a mesh, may share it.
+pub type ObjectId = usize;
+
+/// Hits not made by an object, e.g. probes built to evaluate a material
+pub const NO_OBJECT: ObjectId = 0;
 
+static NEXT_OBJECT_ID: AtomicUsize = AtomicUsize::new(NO_OBJECT + 1);
 
+pub fn new_object_id() -> ObjectId {
+    NEXT_OBJECT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Clone)]
 pub struct HitRecord {
     pub point: Point3,  // intersection point
-    pub normal: Vec3,   // normal at intersection point
+    pub normal: Vec3,   // shading normal at intersection point, materials may perturb it
+    pub geometric_normal: Vec3, // normal of the actual surface at intersection point
+    pub tangent: Vec3,  // shading tangent, follows the direction u grows in
+    pub bitangent: Vec3,    // shading bitangent, follows the direction v grows in
+    pub dpdu: Vec3, // partial derivative of the intersection point with respect to u
+    pub dpdv: Vec3, // partial derivative of the intersection point with respect to v
+    pub dndu: Vec3, // partial derivative of the normal with respect to u, zero for flat surfaces
+    pub dndv: Vec3, // partial derivative of the normal with respect to v
+    pub footprint: Footprint,   // change of the point and (u, v) from one pixel to the next, for texture filtering
     pub material: Arc<dyn Material>,    // material at intersection point
+    pub object_id: ObjectId,    // object the intersection point is on, set by the object after creating the record
     pub t: f64, // intersection_point = origin + direction * t
     pub u: f64, // row of texture coordinate
     pub v: f64, // column of texture coordinate
@@ -23,10 +50,138 @@ pub struct HitRecord {
 impl HitRecord {
     pub fn new(point: Point3, normal: Vec3, material: Arc<dyn Material>, t: f64, u: f64, v: f64, ray: Ray) -> HitRecord {
         let (front_face, outward_normal) = set_face_normal(ray, normal);
-        HitRecord { point, normal: outward_normal, material, t, u, v, front_face }
+        let (tangent, bitangent) = orthonormal_basis(outward_normal);
+
+        HitRecord {
+            point,
+            normal: outward_normal,
+            geometric_normal: outward_normal,
+            tangent,
+            bitangent,
+            dpdu: tangent,
+            dpdv: bitangent,
+            dndu: Vec3::zero(),
+            dndv: Vec3::zero(),
+            footprint: Footprint::default(),
+            object_id: NO_OBJECT,
+            material, t, u, v, front_face
+        }
+    }
+
+    /// The surface at a known point, as if hit head-on from the outside.
+    /// For surfaces found through their parametrization rather than by a ray.
+    pub fn new_head_on(point: Point3, outward_normal: Vec3, material: Arc<dyn Material>, u: f64, v: f64) -> HitRecord {
+        let ray = Ray::new(point + outward_normal, -outward_normal, 0.0);
+
+        HitRecord::new(point, outward_normal, material, 1.0, u, v, ray)
+    }
+
+    /// Set how the surface point changes with the texture coordinates,
+    /// and rebuild the shading tangent frame from it.
+    /// Surfaces without a parametrization keep an arbitrary tangent frame.
+    pub fn set_uv_derivatives(&mut self, dpdu: Vec3, dpdv: Vec3) {
+        self.dpdu = dpdu;
+        self.dpdv = dpdv;
+        self.update_tangent_frame();
+    }
+
+    /// Set how the outward normal changes with the texture coordinates, for curved surfaces.
+    /// Flipped along with the normal when the surface is hit from the inside.
+    pub fn set_normal_derivatives(&mut self, dndu: Vec3, dndv: Vec3) {
+        let side = if self.front_face { 1.0 } else { -1.0 };
+        self.dndu = side * dndu;
+        self.dndv = side * dndv;
+    }
+
+    /// Find the footprint of the ray's pixel by intersecting its differentials with
+    /// the plane tangent to the surface. Left empty for rays without differentials.
+    pub fn set_differentials(&mut self, ray: &Ray) {
+        let Some(differentials) = ray.differentials else {
+            self.footprint = Footprint::default();
+            return;
+        };
+
+        let normal = self.geometric_normal;
+        let offset_point = |origin: Point3, direction: Vec3| {
+            let denominator = normal.dot(direction);
+            if denominator.abs() < 1e-12 {
+                return Vec3::zero();
+            }
+            origin + (normal.dot(self.point - origin) / denominator) * direction - self.point
+        };
+        let dpdx = offset_point(differentials.rx_origin, differentials.rx_direction);
+        let dpdy = offset_point(differentials.ry_origin, differentials.ry_direction);
+
+        self.set_footprint(dpdx, dpdy);
+    }
+
+    /// Set the texture footprint from how much the intersection point moves
+    /// from one pixel to the next along the image's x and y.
+    /// Least squares solution of dP/dx = dP/du * du/dx + dP/dv * dv/dx, same for y.
+    pub fn set_footprint(&mut self, dpdx: Vec3, dpdy: Vec3) {
+        let uu = self.dpdu.dot(self.dpdu);
+        let uv = self.dpdu.dot(self.dpdv);
+        let vv = self.dpdv.dot(self.dpdv);
+        let determinant = uu * vv - uv * uv;
+        if determinant.abs() < 1e-12 {
+            self.footprint = Footprint { dpdx, dpdy, ..Footprint::default() };
+            return;
+        }
+
+        let solve = |dp: Vec3| {
+            let (pu, pv) = (self.dpdu.dot(dp), self.dpdv.dot(dp));
+            ((vv * pu - uv * pv) / determinant, (uu * pv - uv * pu) / determinant)
+        };
+        let (dudx, dvdx) = solve(dpdx);
+        let (dudy, dvdy) = solve(dpdy);
+
+        self.footprint = Footprint { dpdx, dpdy, dudx, dvdx, dudy, dvdy };
+    }
+
+    /// How much the normal changes from one pixel to the next along x and y
+    pub fn normal_differentials(&self) -> (Vec3, Vec3) {
+        let footprint = &self.footprint;
+
+        (
+            footprint.dudx * self.dndu + footprint.dvdx * self.dndv,
+            footprint.dudy * self.dndu + footprint.dvdy * self.dndv
+        )
+    }
+
+    /// Replace the shading normal, the geometric normal is left as is.
+    /// The normal is expected to be on the same side of the surface as the ray.
+    pub fn set_shading_normal(&mut self, normal: Vec3) {
+        self.normal = normal.unit();
+        self.update_tangent_frame();
+    }
+
+    /// Gram-Schmidt dP/du against the shading normal, so the tangent frame stays
+    /// orthonormal after the normal was perturbed.
+    fn update_tangent_frame(&mut self) {
+        let tangent = self.dpdu - self.normal * self.normal.dot(self.dpdu);
+        if tangent.near_zero() {
+            // Degenerate parametrization, e.g. sphere poles
+            (self.tangent, self.bitangent) = orthonormal_basis(self.normal);
+            return;
+        }
+
+        self.tangent = tangent.unit();
+        self.bitangent = self.normal.cross(self.tangent);
+        if self.bitangent.dot(self.dpdv) < 0.0 {
+            self.bitangent = -self.bitangent;
+        }
     }
 }
 
+/// Whether a candidate hit on a surface counts, according to its material's opacity.
+/// Partially opaque surfaces are hit stochastically, so on average the right
+/// fraction of rays passes through them.
+pub fn passes_alpha_test(material: &Arc<MaterialSync>, u: f64, v: f64, point: &Point3, normal: &Vec3) -> bool {
+    let opacity = material.opacity(u, v, point, normal);
+
+    opacity >= 1.0 || (opacity > 0.0 && random::<f64>() < opacity)
+}
+
 /// Sets the normal to always face away from the surface the ray hit
 fn set_face_normal(ray: Ray, normal: Vec3) -> (bool, Vec3) {
     let front_face = ray.direction.dot(normal) < 0.0;
@@ -39,11 +194,29 @@ fn set_face_normal(ray: Ray, normal: Vec3) -> (bool, Vec3) {
 pub trait Hittable {
     fn hit(&self, ray: Ray, ray_interval: Interval) -> Option<HitRecord>;
     fn bounding_box(&self) -> AABB;
+
+    /// The light emitting surfaces in this hittable, to be sampled directly by the camera.
+    /// Emission seen after a bounce that sampled emitters directly is skipped for the
+    /// emitters in the light tree, so emitters not reported here are only found by scattering.
+    fn emitters(&self) -> Vec<Arc<EmitterSync>> {
+        Vec::new()
+    }
 }
 
 // ? wtf is this, read about it
 pub type HittableSync = dyn Hittable + Send + Sync;
 
+/// Surfaces that can be walked by their texture coordinates, e.g. to bake lighting into a texture.
+pub trait UvSurface {
+    /// The surface at the texture coordinates (u, v), seen head-on from the outside.
+    /// None where no part of the surface maps to (u, v).
+    fn surface_at(&self, u: f64, v: f64) -> Option<HitRecord>;
+
+    fn material(&self) -> Arc<MaterialSync>;
+}
+
+pub type UvSurfaceSync = dyn UvSurface + Send + Sync;
+
 /// A Hittable container for objects.
 pub struct HittableComposite {
     bbox: AABB,
@@ -72,6 +245,10 @@ impl Hittable for HittableComposite {
 
         final_hrec
     }
+
+    fn emitters(&self) -> Vec<Arc<EmitterSync>> {
+        self.objects.iter().flat_map(|obj| obj.emitters()).collect()
+    }
 }
 
 impl HittableComposite {
@@ -124,3 +301,84 @@ impl HittableComposite {
         color
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::RayDifferentials;
+    use crate::graphics::material::Lambertian;
+
+    /// A hit on the y = 0 plane, seen from above
+    fn plane_hit(dpdu: Vec3, dpdv: Vec3) -> HitRecord {
+        let mut hitrec = HitRecord::new_head_on(
+            Point3::zero(),
+            Vec3::new(0.0, 1.0, 0.0),
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+            0.5,
+            0.5
+        );
+        hitrec.set_uv_derivatives(dpdu, dpdv);
+        hitrec
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "got {actual}, expected {expected}");
+    }
+
+    #[test]
+    fn footprint_solves_scaled_axes() {
+        let mut hitrec = plane_hit(Vec3::new(2.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 3.0));
+        hitrec.set_footprint(Vec3::new(1.0, 0.0, 0.6), Vec3::new(-0.4, 0.0, 1.5));
+
+        let footprint = hitrec.footprint;
+        assert_close(footprint.dudx, 0.5);
+        assert_close(footprint.dvdx, 0.2);
+        assert_close(footprint.dudy, -0.2);
+        assert_close(footprint.dvdy, 0.5);
+    }
+
+    #[test]
+    fn footprint_solves_skewed_axes_and_ignores_the_normal() {
+        let (dpdu, dpdv) = (Vec3::new(1.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 1.0));
+        let mut hitrec = plane_hit(dpdu, dpdv);
+        // Off the tangent plane, the least squares solution drops the normal component
+        let off_plane = Vec3::new(0.0, 5.0, 0.0);
+        hitrec.set_footprint(0.3 * dpdu + 0.7 * dpdv + off_plane, -0.25 * dpdu + 2.0 * dpdv);
+
+        let footprint = hitrec.footprint;
+        assert_close(footprint.dudx, 0.3);
+        assert_close(footprint.dvdx, 0.7);
+        assert_close(footprint.dudy, -0.25);
+        assert_close(footprint.dvdy, 2.0);
+    }
+
+    #[test]
+    fn footprint_without_parametrization_keeps_only_the_point_differentials() {
+        let mut hitrec = plane_hit(Vec3::zero(), Vec3::new(0.0, 0.0, 1.0));
+        hitrec.set_footprint(Vec3::new(0.1, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.1));
+
+        let footprint = hitrec.footprint;
+        assert_close(footprint.dpdx.x(), 0.1);
+        assert_close(footprint.dpdy.z(), 0.1);
+        assert_eq!((footprint.dudx, footprint.dvdx, footprint.dudy, footprint.dvdy), (0.0, 0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn differentials_are_intersected_with_the_tangent_plane() {
+        let mut hitrec = plane_hit(Vec3::new(4.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 4.0));
+        let mut ray = Ray::new(Point3::new(0.0, 2.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0);
+        ray.differentials = Some(RayDifferentials {
+            rx_origin: Point3::new(0.0, 2.0, 0.0),
+            rx_direction: Vec3::new(0.1, -1.0, 0.0),
+            ry_origin: Point3::new(0.0, 2.0, 0.2),
+            ry_direction: Vec3::new(0.0, -1.0, 0.0)
+        });
+        hitrec.set_differentials(&ray);
+
+        let footprint = hitrec.footprint;
+        assert_close(footprint.dpdx.x(), 0.2);
+        assert_close(footprint.dudx, 0.05);
+        assert_close(footprint.dvdy, 0.05);
+        assert_close(footprint.dudy, 0.0);
+    }
+}
@@ -6,9 +6,12 @@ mod translate;
 mod rotate;
 mod ray;
 mod constant_medium;
+mod subsurface;
+mod triangle;
+mod displacement;
 pub mod hittable;
 
-pub use ray::Ray;
+pub use ray::{Ray, RayDifferentials};
 pub use quad::Quad;
 pub use boxx::box_new;
 pub use sphere::Sphere;
@@ -16,3 +19,6 @@ pub use rotate::RotateY;
 pub use translate::Translate;
 pub use sphereflake::new_sphereflake_upright;
 pub use constant_medium::ConstantMedium;
+pub use subsurface::SubsurfaceMedium;
+pub use triangle::{Triangle, Vertex};
+pub use displacement::{Displacement, Tessellation};
@@ -1,7 +1,9 @@
 use std::sync::Arc;
 
-use crate::graphics::{aabb::AABB, material::MaterialSync};
-use crate::geometry::{Ray, hittable::{Hittable, HitRecord}};
+use rand::random;
+
+use crate::graphics::{aabb::AABB, material::MaterialSync, light_bvh::{Emitter, EmitterSync, LightBounds, emitted_luminance}};
+use crate::geometry::{Ray, hittable::{Hittable, HitRecord, ObjectId, UvSurface, new_object_id, passes_alpha_test}};
 use crate::{
     math::interval::Interval,
     math::vec3::{Point3, Vec3},
@@ -17,6 +19,7 @@ use crate::{
 /// Defined by the plane equation: Ax + By + Cz = D
 /// The plane normal is (A, B, C)
 /// Any point (v) on the plane satisfies n ⋅ v = D
+#[derive(Clone)]
 pub struct Quad {
     bottom_left: Point3,
     u: Vec3,
@@ -25,7 +28,8 @@ pub struct Quad {
     d: f64,
     w: Vec3,
     material: Arc<MaterialSync>,
-    bounding_box: AABB
+    bounding_box: AABB,
+    id: ObjectId
 }
 
 impl Quad {
@@ -40,9 +44,15 @@ impl Quad {
             d: normal.dot(_bottom_left),
             w: n / n.dot(n),
             material: _material,
-            bounding_box: AABB::new_from_points(_bottom_left, _bottom_left + _u + _v).pad()
+            bounding_box: AABB::new_from_points(_bottom_left, _bottom_left + _u + _v).pad(),
+            id: new_object_id()
         }
     }
+
+    /// Make the quad part of a larger object, its hits report that object's ID
+    pub fn with_object_id(self, id: ObjectId) -> Self {
+        Quad { id, ..self }
+    }
 }
 
 impl Hittable for Quad {
@@ -74,7 +84,12 @@ impl Hittable for Quad {
             return None;
         }
 
-        Some(HitRecord::new(
+        // Cut out parts are not there as far as the ray is concerned
+        if !passes_alpha_test(&self.material, alpha, beta, &intersection, &self.normal) {
+            return None;
+        }
+
+        let mut hitrec = HitRecord::new(
             intersection,
             self.normal,
             self.material.clone(),
@@ -82,6 +97,72 @@ impl Hittable for Quad {
             alpha,
             beta,
             ray
-        ))
+        );
+        // Texture coordinates are the fractions along the u, v edges
+        hitrec.set_uv_derivatives(self.u, self.v);
+        hitrec.object_id = self.id;
+
+        Some(hitrec)
+    }
+
+    fn emitters(&self) -> Vec<Arc<EmitterSync>> {
+        if self.light_bounds().power > 0.0 { vec![Arc::new(self.clone())] } else { Vec::new() }
+    }
+}
+
+impl UvSurface for Quad {
+    fn surface_at(&self, u: f64, v: f64) -> Option<HitRecord> {
+        if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+            return None;
+        }
+
+        let mut hitrec = HitRecord::new_head_on(
+            self.bottom_left + u * self.u + v * self.v,
+            self.normal,
+            self.material.clone(),
+            u,
+            v
+        );
+        hitrec.set_uv_derivatives(self.u, self.v);
+        hitrec.object_id = self.id;
+
+        Some(hitrec)
+    }
+
+    fn material(&self) -> Arc<MaterialSync> {
+        self.material.clone()
+    }
+}
+
+impl Emitter for Quad {
+    /// Sample the quad's area uniformly
+    fn sample_point(&self, origin: Point3) -> Option<(Point3, f64)> {
+        let point = self.bottom_left + random::<f64>() * self.u + random::<f64>() * self.v;
+        let to_point = point - origin;
+        let distance_squared = to_point.length_squared();
+        let cos_light = self.normal.dot(to_point).abs() / distance_squared.sqrt();
+        if cos_light < NEAR_ZERO_THRESHOLD {
+            return None;
+        }
+
+        let area = self.u.cross(self.v).length();
+        Some((point, distance_squared / (cos_light * area)))
+    }
+
+    fn light_bounds(&self) -> LightBounds {
+        // Centers of a 4x4 grid of cells
+        let points: Vec<(Point3, Vec3, f64, f64)> = (0..16).map(|i| {
+            let alpha = (i % 4) as f64 / 4.0 + 0.125;
+            let beta = (i / 4) as f64 / 4.0 + 0.125;
+
+            (self.bottom_left + alpha * self.u + beta * self.v, self.normal, alpha, beta)
+        }).collect();
+        let (front, back) = emitted_luminance(&self.material, &points);
+
+        LightBounds::new_surface(self.bounding_box, self.normal, front, back, self.u.cross(self.v).length())
+    }
+
+    fn object_id(&self) -> ObjectId {
+        self.id
     }
 }
\ No newline at end of file
@@ -1,10 +1,24 @@
 use crate::math::vec3::{Vec3, Point3};
+use crate::graphics::medium::MediumStack;
+use crate::geometry::hittable::HitRecord;
+
+/// Rays offset by one pixel along the image's x and y, traced alongside the main ray
+/// to tell how much of the scene a pixel covers wherever the ray ends up.
+#[derive(Clone, Copy)]
+pub struct RayDifferentials {
+    pub rx_origin: Point3,
+    pub rx_direction: Vec3,
+    pub ry_origin: Point3,
+    pub ry_direction: Vec3
+}
 
 #[derive(Clone, Copy)]
 pub struct Ray {
     pub origin: Point3,
     pub direction: Vec3,
-    pub time: f64
+    pub time: f64,
+    pub media: MediumStack,  // media enclosing the path this ray is part of
+    pub differentials: Option<RayDifferentials> // only known for camera rays and their specular bounces
 }
 
 impl Ray {
@@ -13,17 +27,89 @@ impl Ray {
         Ray { 
             origin: Vec3::zero(),
             direction: Vec3::zero(),
-            time: 0.0
+            time: 0.0,
+            media: MediumStack::default(),
+            differentials: None
         }
     }
     pub fn new(origin: Point3, direction: Vec3, time: f64) -> Ray { 
         Ray { 
             origin,
             direction,
-            time
+            time,
+            media: MediumStack::default(),
+            differentials: None
+        }
+    }
+
+    /// Continue the path of this ray from a new origin in a new direction.
+    /// The time and the enclosing media are carried over.
+    /// Differentials are dropped, after a diffuse bounce a pixel covers too much to track.
+    pub fn spawn(&self, origin: Point3, direction: Vec3) -> Ray {
+        Ray {
+            origin,
+            direction,
+            differentials: None,
+            ..*self
         }
     }
 
+    /// Continue the path with a mirror reflection off the hit surface,
+    /// carrying the differentials over as described in Igehy's "Tracing Ray Differentials".
+    pub fn spawn_reflected(&self, hitrec: &HitRecord, direction: Vec3) -> Ray {
+        let mut reflected = self.spawn(hitrec.point, direction);
+        reflected.differentials = self.differentials.map(|differentials| {
+            let wo = -self.direction.unit();
+            let wi = direction.unit();
+            let normal = hitrec.normal;
+            let cos_theta = wo.dot(normal);
+
+            let reflect = |origin_offset: Vec3, offset_direction: Vec3, dndx: Vec3| {
+                let dwodx = -offset_direction.unit() - wo;
+                let dcosdx = dwodx.dot(normal) + wo.dot(dndx);
+
+                (hitrec.point + origin_offset, wi - dwodx + 2.0 * (cos_theta * dndx + dcosdx * normal))
+            };
+            let (dndx, dndy) = hitrec.normal_differentials();
+            let (rx_origin, rx_direction) = reflect(hitrec.footprint.dpdx, differentials.rx_direction, dndx);
+            let (ry_origin, ry_direction) = reflect(hitrec.footprint.dpdy, differentials.ry_direction, dndy);
+
+            RayDifferentials { rx_origin, rx_direction, ry_origin, ry_direction }
+        });
+
+        reflected
+    }
+
+    /// Continue the path refracted through the hit surface, eta is the ratio of the
+    /// index of refraction the ray comes from over the one it goes into.
+    pub fn spawn_refracted(&self, hitrec: &HitRecord, direction: Vec3, eta: f64) -> Ray {
+        let mut refracted = self.spawn(hitrec.point, direction);
+        refracted.differentials = self.differentials.map(|differentials| {
+            let wo = -self.direction.unit();
+            let wi = direction.unit();
+            let normal = hitrec.normal;
+            let cos_incident = wo.dot(normal);
+            let cos_transmitted = wi.dot(normal).abs().max(1e-8);
+
+            // wi = -eta * wo + mu * n, differentiated along x and y
+            let mu = eta * cos_incident - cos_transmitted;
+            let refract = |origin_offset: Vec3, offset_direction: Vec3, dndx: Vec3| {
+                let dwodx = -offset_direction.unit() - wo;
+                let dcosdx = dwodx.dot(normal) + wo.dot(dndx);
+                let dmudx = (eta - eta * eta * cos_incident / cos_transmitted) * dcosdx;
+
+                (hitrec.point + origin_offset, wi - eta * dwodx + mu * dndx + dmudx * normal)
+            };
+            let (dndx, dndy) = hitrec.normal_differentials();
+            let (rx_origin, rx_direction) = refract(hitrec.footprint.dpdx, differentials.rx_direction, dndx);
+            let (ry_origin, ry_direction) = refract(hitrec.footprint.dpdy, differentials.ry_direction, dndy);
+
+            RayDifferentials { rx_origin, rx_direction, ry_origin, ry_direction }
+        });
+
+        refracted
+    }
+
     pub fn at(&self, t: f64) -> Point3 {
         self.origin + t * self.direction
     }
@@ -5,10 +5,10 @@
 use std::sync::Arc;
 
 use crate::geometry::Ray;
-use crate::graphics::aabb::AABB;
-use crate::math::{vec3::Vec3, interval::Interval};
+use crate::graphics::{aabb::AABB, light_bvh::{Emitter, EmitterSync, LightBounds}};
+use crate::math::{vec3::{Point3, Vec3}, interval::Interval};
 
-use super::hittable::{Hittable, HitRecord, HittableSync};
+use super::hittable::{Hittable, HitRecord, HittableSync, ObjectId};
 
 /// "Rotate" a hittable around Y axis
 /// The hittable isn't actually rotated, but rather the incident rays are rotated
@@ -20,37 +20,42 @@ pub struct RotateY {
 
 impl RotateY {
     pub fn new(object: Arc<HittableSync>, angle: f64) -> Self {
-        let bbox = object.bounding_box();
-
-        let mut min = Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
-        let mut max = Vec3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
-
-        // Find min, max points of bounding box post rotation
-        for i in 0..2 {
-            for j in 0..2 {
-                for k in 0..2 {
-                    let x = i as f64 * bbox.x.max + (1.0 - i as f64) * bbox.x.min;
-                    let y = j as f64 * bbox.y.max + (1.0 - j as f64) * bbox.y.min;
-                    let z = k as f64 * bbox.z.max + (1.0 - k as f64) * bbox.z.min;
-                    
-                    let tester = Vec3::new(x, y, z).rotate_y(angle.to_radians());
-
-                    for i in 0..3 {
-                        min[i] = min[i].min(tester[i]);
-                        max[i] = max[i].max(tester[i]);
-                    }
-                }
-            }
-        }
+        let bounding_box = rotated_bounding_box(object.bounding_box(), angle);
 
         RotateY { 
             object,
             theta: angle,
-            bounding_box: AABB::new_from_points(min, max) 
+            bounding_box
         }
     }
 }
 
+/// Bounding box of a rotated bounding box, angle in degrees
+fn rotated_bounding_box(bbox: AABB, angle: f64) -> AABB {
+    let mut min = Vec3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+    let mut max = Vec3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+
+    // Find min, max points of bounding box post rotation
+    for i in 0..2 {
+        for j in 0..2 {
+            for k in 0..2 {
+                let x = i as f64 * bbox.x.max + (1.0 - i as f64) * bbox.x.min;
+                let y = j as f64 * bbox.y.max + (1.0 - j as f64) * bbox.y.min;
+                let z = k as f64 * bbox.z.max + (1.0 - k as f64) * bbox.z.min;
+                
+                let tester = Vec3::new(x, y, z).rotate_y(angle.to_radians());
+
+                for i in 0..3 {
+                    min[i] = min[i].min(tester[i]);
+                    max[i] = max[i].max(tester[i]);
+                }
+            }
+        }
+    }
+
+    AABB::new_from_points(min, max)
+}
+
 impl Hittable for RotateY {
     fn bounding_box(&self) -> AABB {
         self.bounding_box
@@ -72,7 +77,50 @@ impl Hittable for RotateY {
         let mut hitrec = hitrec_result.unwrap();
         hitrec.point = hitrec.point.rotate_y(self.theta.to_radians());
         hitrec.normal = hitrec.normal.rotate_y(self.theta.to_radians());
+        hitrec.geometric_normal = hitrec.geometric_normal.rotate_y(self.theta.to_radians());
+        hitrec.tangent = hitrec.tangent.rotate_y(self.theta.to_radians());
+        hitrec.bitangent = hitrec.bitangent.rotate_y(self.theta.to_radians());
+        hitrec.dpdu = hitrec.dpdu.rotate_y(self.theta.to_radians());
+        hitrec.dpdv = hitrec.dpdv.rotate_y(self.theta.to_radians());
+        hitrec.dndu = hitrec.dndu.rotate_y(self.theta.to_radians());
+        hitrec.dndv = hitrec.dndv.rotate_y(self.theta.to_radians());
 
         Some(hitrec)
     }
+
+    fn emitters(&self) -> Vec<Arc<EmitterSync>> {
+        self.object
+            .emitters()
+            .into_iter()
+            .map(|emitter| Arc::new(RotatedEmitter { emitter, theta: self.theta }) as Arc<EmitterSync>)
+            .collect()
+    }
+}
+
+/// An emitter inside a RotateY, sampled in object space
+struct RotatedEmitter {
+    emitter: Arc<EmitterSync>,
+    theta: f64
+}
+
+impl Emitter for RotatedEmitter {
+    fn sample_point(&self, origin: Point3) -> Option<(Point3, f64)> {
+        let (point, pdf) = self.emitter.sample_point(origin.rotate_y(-self.theta.to_radians()))?;
+
+        Some((point.rotate_y(self.theta.to_radians()), pdf))
+    }
+
+    fn light_bounds(&self) -> LightBounds {
+        let bounds = self.emitter.light_bounds();
+
+        LightBounds {
+            bbox: rotated_bounding_box(bounds.bbox, self.theta),
+            axis: bounds.axis.rotate_y(self.theta.to_radians()),
+            ..bounds
+        }
+    }
+
+    fn object_id(&self) -> ObjectId {
+        self.emitter.object_id()
+    }
 }
\ No newline at end of file
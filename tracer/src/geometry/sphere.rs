@@ -1,16 +1,24 @@
 use std::sync::Arc;
 use std::f64::consts::PI;
 
-use crate::graphics::{material::MaterialSync, aabb::AABB};
-use crate::math::{interval::Interval, vec3::{Vec3, Point3}};
-use crate::geometry::{Ray, hittable::{Hittable, HitRecord}};
+use rand::random;
+
+use crate::graphics::{material::MaterialSync, aabb::AABB, light_bvh::{Emitter, EmitterSync, LightBounds, emitted_luminance}};
+use crate::math::{
+    interval::Interval,
+    vec3::{Vec3, Point3},
+    consts::NEAR_ZERO_THRESHOLD,
+    utils::{get_random_point_on_unit_sphere, orthonormal_basis}
+};
+use crate::geometry::{Ray, hittable::{Hittable, HitRecord, ObjectId, UvSurface, new_object_id, passes_alpha_test}};
 
 #[derive(Clone)]
 pub struct Sphere {
     pub center: Point3,
     pub radius: f64,
     pub material: Arc<MaterialSync>,
-    pub bounding_box: AABB
+    pub bounding_box: AABB,
+    pub id: ObjectId
 }
 
 impl Sphere {
@@ -20,7 +28,8 @@ impl Sphere {
         Sphere {
             center: center,
             radius, material,
-            bounding_box: AABB::new_from_points(center - radius_vec, center + radius_vec)
+            bounding_box: AABB::new_from_points(center - radius_vec, center + radius_vec),
+            id: new_object_id()
         } 
     }
 
@@ -40,6 +49,28 @@ impl Sphere {
 
         ((phi / (2.0 * PI)), (theta / PI))
     }
+
+    /// p: a given point on the sphere of radius one, centered at the origin.
+    /// Returns the partial derivatives (dP/du, dP/dv) of the sphere's surface at that point
+    /// following the parametrization of get_sphere_uv().
+    /// x = -sin(theta)cos(phi), y = -cos(theta), z = sin(theta)sin(phi)
+    fn get_sphere_uv_derivatives(&self, point: Point3) -> (Vec3, Vec3) {
+        let sin_theta = (1.0 - point.y() * point.y()).max(0.0).sqrt();
+        let dpdu = Vec3::new(point.z(), 0.0, -point.x()) * (2.0 * PI * self.radius);
+
+        // At the poles dP/dv has no defined direction along phi
+        if sin_theta < NEAR_ZERO_THRESHOLD {
+            return (dpdu, Vec3::zero());
+        }
+
+        let dpdv = Vec3::new(
+            -point.x() * point.y() / sin_theta,
+            sin_theta,
+            -point.y() * point.z() / sin_theta
+        ) * (PI * self.radius);
+
+        (dpdu, dpdv)
+    }
 }
 
 impl Hittable for Sphere {
@@ -77,31 +108,134 @@ impl Hittable for Sphere {
     
         if discriminant < 0.0 { return None; }
         let dscr_sqrt = discriminant.sqrt();
-        let mut root = (-half_b - dscr_sqrt) / a;
 
-        if !ray_interval.fully_contains(root) {
-            root = (-half_b + dscr_sqrt) / a;
-            if !ray_interval.fully_contains(root) { return None; }
+        // Try the near root first, the far one is used if the near root is out of
+        // range, or if the surface is transparent there
+        for root in [(-half_b - dscr_sqrt) / a, (-half_b + dscr_sqrt) / a] {
+            if !ray_interval.fully_contains(root) { continue; }
+
+            let point = ray.at(root);
+            let outward_normal = (point - center) / self.radius;
+            let (u, v) = self.get_sphere_uv(outward_normal);
+            if !passes_alpha_test(&self.material, u, v, &point, &outward_normal) { continue; }
+
+            let mut rec = HitRecord::new(
+                point,
+                outward_normal,
+                self.material.clone(),
+                root,
+                u,
+                v,
+                ray
+            );
+            let (dpdu, dpdv) = self.get_sphere_uv_derivatives(outward_normal);
+            rec.set_uv_derivatives(dpdu, dpdv);
+            rec.set_normal_derivatives(dpdu / self.radius, dpdv / self.radius);
+            rec.object_id = self.id;
+
+            return Some(rec);
         }
 
-        let point = ray.at(root);
-        let outward_normal = (point - center) / self.radius;
-        let (u, v) = self.get_sphere_uv(outward_normal);
+        None
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bounding_box
+    }
+
+    fn emitters(&self) -> Vec<Arc<EmitterSync>> {
+        if self.light_bounds().power > 0.0 { vec![Arc::new(self.clone())] } else { Vec::new() }
+    }
+}
+
+impl UvSurface for Sphere {
+    /// Inverse of get_sphere_uv(), u goes around the Y axis and v from the bottom pole to the top
+    fn surface_at(&self, u: f64, v: f64) -> Option<HitRecord> {
+        let phi = 2.0 * PI * u;
+        let theta = PI * v;
+        let outward_normal = Vec3::new(-theta.sin() * phi.cos(), -theta.cos(), theta.sin() * phi.sin());
 
-        let rec = HitRecord::new(
-            point,
+        let mut rec = HitRecord::new_head_on(
+            self.center + self.radius * outward_normal,
             outward_normal,
             self.material.clone(),
-            root,
             u,
-            v,
-            ray
+            v
         );
+        let (dpdu, dpdv) = self.get_sphere_uv_derivatives(outward_normal);
+        rec.set_uv_derivatives(dpdu, dpdv);
+        rec.set_normal_derivatives(dpdu / self.radius, dpdv / self.radius);
+        rec.object_id = self.id;
 
         Some(rec)
     }
 
-    fn bounding_box(&self) -> AABB {
-        self.bounding_box
+    fn material(&self) -> Arc<MaterialSync> {
+        self.material.clone()
+    }
+}
+
+impl Emitter for Sphere {
+    /// From the outside, sample the cone of directions the sphere covers.
+    /// From the inside, sample its whole surface.
+    fn sample_point(&self, origin: Point3) -> Option<(Point3, f64)> {
+        let to_center = self.center - origin;
+        let distance_squared = to_center.length_squared();
+        let radius_squared = self.radius * self.radius;
+
+        if distance_squared <= radius_squared {
+            let normal = get_random_point_on_unit_sphere();
+            let point = self.center + self.radius * normal;
+            let to_point = point - origin;
+            let cos_light = normal.dot(to_point.unit()).abs();
+            if cos_light < NEAR_ZERO_THRESHOLD {
+                return None;
+            }
+
+            let area = 4.0 * PI * radius_squared;
+            return Some((point, to_point.length_squared() / (cos_light * area)));
+        }
+
+        let distance = distance_squared.sqrt();
+        let cos_theta_max = (1.0 - radius_squared / distance_squared).max(0.0).sqrt();
+        let cos_theta = 1.0 + random::<f64>() * (cos_theta_max - 1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random::<f64>();
+
+        let axis = to_center / distance;
+        let (tangent, bitangent) = orthonormal_basis(axis);
+        let direction = sin_theta * phi.cos() * tangent + sin_theta * phi.sin() * bitangent + cos_theta * axis;
+
+        // Nearest intersection along the direction
+        let t = distance * cos_theta - (radius_squared - distance_squared * sin_theta * sin_theta).max(0.0).sqrt();
+
+        Some((origin + t * direction, 1.0 / (2.0 * PI * (1.0 - cos_theta_max))))
+    }
+
+    fn light_bounds(&self) -> LightBounds {
+        // Fixed spread of points, emission is estimated from the outside
+        let count = 16;
+        let points: Vec<(Point3, Vec3, f64, f64)> = (0..count).map(|i| {
+            let y = 1.0 - (2.0 * i as f64 + 1.0) / count as f64;
+            let ring = (1.0 - y * y).sqrt();
+            let phi = i as f64 * PI * (3.0 - 5.0_f64.sqrt());
+            let normal = Vec3::new(ring * phi.cos(), y, ring * phi.sin());
+            let (u, v) = self.get_sphere_uv(normal);
+
+            (self.center + self.radius * normal, normal, u, v)
+        }).collect();
+        let (outside, _) = emitted_luminance(&self.material, &points);
+
+        LightBounds {
+            bbox: self.bounding_box,
+            axis: Vec3::new(0.0, 1.0, 0.0),
+            theta_normals: PI,
+            theta_emission: PI / 2.0,
+            power: outside * PI * 4.0 * PI * self.radius * self.radius
+        }
+    }
+
+    fn object_id(&self) -> ObjectId {
+        self.id
     }
 }
\ No newline at end of file
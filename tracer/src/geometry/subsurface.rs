@@ -0,0 +1,163 @@
+use rand::random;
+use std::sync::Arc;
+
+use crate::geometry::{Ray, hittable::{Hittable, HitRecord, HittableSync}};
+use crate::graphics::{aabb::AABB, material::Material};
+use crate::math::{
+    interval::Interval,
+    optics::{reflect, refract, reflectance},
+    utils::get_random_point_on_unit_sphere,
+    vec3::{Color, Vec3}
+};
+use crate::rendering::color::COLOR_WHITE;
+
+/// Scattering events after which a random walk is considered absorbed
+const MAX_WALK_STEPS: usize = 256;
+
+/// Start walk segments slightly off the boundary so they don't hit it again right away
+const WALK_T_MIN: f64 = 0.0001;
+
+
+/// Subsurface scattering for skin, wax, marble, etc.
+///
+/// Like ConstantMedium, the volume is defined by a closed boundary hittable.
+/// Unlike ConstantMedium, the boundary is a surface too: light refracts into it,
+/// random walks through the volume, and comes out wherever the walk reaches the
+/// boundary again, possibly far from where it entered.
+///
+/// Anything placed inside the boundary is ignored by the walk.
+pub struct SubsurfaceMedium {
+    boundry: Arc<HittableSync>,
+    material: Arc<SubsurfaceMaterial>
+}
+
+impl SubsurfaceMedium {
+    /// Scattering and absorption are coefficients per unit length, per channel.
+    pub fn new_coefficients(boundry: Arc<HittableSync>, ior: f64, scattering: Color, absorption: Color) -> Self {
+        SubsurfaceMedium {
+            boundry: boundry.clone(),
+            material: Arc::new(SubsurfaceMaterial {
+                boundry,
+                ior,
+                sigma_s: scattering,
+                sigma_t: scattering + absorption
+            })
+        }
+    }
+
+    /// albedo is the chance of scattering rather than absorbing on every interaction,
+    /// mean_free_path is the average distance between interactions, per channel.
+    pub fn new_mean_free_path(boundry: Arc<HittableSync>, ior: f64, albedo: Color, mean_free_path: Color) -> Self {
+        let sigma_t = Color::new(
+            1.0 / mean_free_path.x(),
+            1.0 / mean_free_path.y(),
+            1.0 / mean_free_path.z()
+        );
+
+        SubsurfaceMedium {
+            boundry: boundry.clone(),
+            material: Arc::new(SubsurfaceMaterial { boundry, ior, sigma_s: albedo * sigma_t, sigma_t })
+        }
+    }
+}
+
+impl Hittable for SubsurfaceMedium {
+    fn hit(&self, ray: Ray, ray_interval: Interval) -> Option<HitRecord> {
+        let mut hitrec = self.boundry.hit(ray, ray_interval)?;
+        hitrec.material = self.material.clone();
+
+        Some(hitrec)
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.boundry.bounding_box()
+    }
+}
+
+
+pub struct SubsurfaceMaterial {
+    boundry: Arc<HittableSync>,
+    ior: f64,
+    sigma_s: Color, // scattering coefficient
+    sigma_t: Color  // extinction coefficient, scattering + absorption
+}
+
+impl SubsurfaceMaterial {
+    /// Transmittance over a distance, per channel
+    fn transmittance(&self, distance: f64) -> Color {
+        Color::new(
+            (-self.sigma_t.x() * distance).exp(),
+            (-self.sigma_t.y() * distance).exp(),
+            (-self.sigma_t.z() * distance).exp()
+        )
+    }
+
+    /// Random walk through the volume, starting at a point on the boundary.
+    /// Returns the throughput of the walk and the ray leaving the boundary.
+    ///
+    /// Each channel has its own extinction, so distances are sampled from a randomly
+    /// picked channel, and weighted by the average pdf over all channels.
+    fn random_walk(&self, incident_ray: &Ray, entry: &HitRecord, direction: Vec3) -> Option<(Color, Ray)> {
+        let mut throughput = COLOR_WHITE;
+        let mut walk_ray = incident_ray.spawn(entry.point, direction);
+
+        for _ in 0..MAX_WALK_STEPS {
+            // Distance to where the walk would leave the volume
+            let exit = self.boundry.hit(walk_ray, Interval::new(WALK_T_MIN, f64::INFINITY))?;
+
+            let channel = (random::<f64>() * 3.0) as usize % 3;
+            let distance = -(1.0 - random::<f64>()).ln() / self.sigma_t[channel];
+
+            if distance < exit.t {
+                // Scatter inside the volume
+                let transmittance = self.transmittance(distance);
+                let pdf = (self.sigma_t * transmittance).dot(COLOR_WHITE) / 3.0;
+
+                throughput = throughput * self.sigma_s * transmittance / pdf;
+                walk_ray = walk_ray.spawn(walk_ray.at(distance), get_random_point_on_unit_sphere());
+                continue;
+            }
+
+            // Reached the boundary
+            let transmittance = self.transmittance(exit.t);
+            throughput = throughput * transmittance / (transmittance.dot(COLOR_WHITE) / 3.0);
+
+            // The exit normal faces back into the volume
+            let unit_direction = walk_ray.direction.unit();
+            let cos_theta = -unit_direction.dot(exit.normal).min(1.0);
+            let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
+
+            let cannot_refract = self.ior * sin_theta > 1.0;
+            if cannot_refract || reflectance(cos_theta, self.ior) > random::<f64>() {
+                walk_ray = walk_ray.spawn(exit.point, reflect(unit_direction, exit.normal));
+                continue;
+            }
+
+            let exit_direction = refract(unit_direction, exit.normal, self.ior);
+            return Some((throughput, walk_ray.spawn(exit.point, exit_direction)));
+        }
+
+        None
+    }
+}
+
+impl Material for SubsurfaceMaterial {
+    fn scatter(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<(Color, Ray)> {
+        // Walks are done in one go, so a ray can only get here from the inside if it
+        // started there. Let it out.
+        if !hitrec.front_face {
+            return Some((COLOR_WHITE, incident_ray.spawn(hitrec.point, incident_ray.direction)));
+        }
+
+        let unit_direction = incident_ray.direction.unit();
+        let cos_theta = -unit_direction.dot(hitrec.normal).min(1.0);
+
+        // Specular reflection off the surface
+        if reflectance(cos_theta, 1.0 / self.ior) > random::<f64>() {
+            return Some((COLOR_WHITE, incident_ray.spawn(hitrec.point, reflect(unit_direction, hitrec.normal))));
+        }
+
+        let direction = refract(unit_direction, hitrec.normal, 1.0 / self.ior);
+        self.random_walk(incident_ray, hitrec, direction)
+    }
+}
@@ -5,9 +5,9 @@
 use std::sync::Arc;
 
 use crate::geometry::Ray;
-use crate::graphics::aabb::AABB;
-use crate::math::{vec3::Vec3, interval::Interval};
-use super::hittable::{Hittable, HitRecord, HittableSync};
+use crate::graphics::{aabb::AABB, light_bvh::{Emitter, EmitterSync, LightBounds}};
+use crate::math::{vec3::{Point3, Vec3}, interval::Interval};
+use super::hittable::{Hittable, HitRecord, HittableSync, ObjectId};
 
 
 pub struct Translate {
@@ -44,4 +44,36 @@ impl Hittable for Translate {
 
         Some(hitrec)
     }
+
+    fn emitters(&self) -> Vec<Arc<EmitterSync>> {
+        self.object
+            .emitters()
+            .into_iter()
+            .map(|emitter| Arc::new(TranslatedEmitter { emitter, offset: self.offset }) as Arc<EmitterSync>)
+            .collect()
+    }
+}
+
+/// An emitter inside a Translate, sampled in object space
+struct TranslatedEmitter {
+    emitter: Arc<EmitterSync>,
+    offset: Vec3
+}
+
+impl Emitter for TranslatedEmitter {
+    fn sample_point(&self, origin: Point3) -> Option<(Point3, f64)> {
+        let (point, pdf) = self.emitter.sample_point(origin - self.offset)?;
+
+        Some((point + self.offset, pdf))
+    }
+
+    fn light_bounds(&self) -> LightBounds {
+        let bounds = self.emitter.light_bounds();
+
+        LightBounds { bbox: bounds.bbox + self.offset, ..bounds }
+    }
+
+    fn object_id(&self) -> ObjectId {
+        self.emitter.object_id()
+    }
 }
\ No newline at end of file
@@ -0,0 +1,274 @@
+use std::sync::Arc;
+
+use rand::random;
+
+use crate::graphics::{material::MaterialSync, aabb::AABB, light_bvh::{Emitter, EmitterSync, LightBounds, emitted_luminance}};
+use crate::geometry::{Ray, hittable::{Hittable, HitRecord, ObjectId, UvSurface, new_object_id, passes_alpha_test}};
+use crate::math::{
+    interval::Interval,
+    vec3::{Point3, Vec3},
+    consts::NEAR_ZERO_THRESHOLD
+};
+
+/// A triangle corner, with the normal and texture coordinates to interpolate across the triangle
+#[derive(Clone, Copy)]
+pub struct Vertex {
+    pub position: Point3,
+    pub normal: Vec3,
+    pub u: f64,
+    pub v: f64
+}
+
+impl Vertex {
+    pub fn new(position: Point3, normal: Vec3, u: f64, v: f64) -> Self {
+        Vertex { position, normal, u, v }
+    }
+
+    /// Weighted sum of three vertices, by barycentric weights
+    pub fn interpolate(vertices: &[Vertex; 3], weights: [f64; 3]) -> Vertex {
+        let mut vertex = Vertex::new(Point3::zero(), Vec3::zero(), 0.0, 0.0);
+        for (corner, weight) in vertices.iter().zip(weights) {
+            vertex.position += weight * corner.position;
+            vertex.normal += weight * corner.normal;
+            vertex.u += weight * corner.u;
+            vertex.v += weight * corner.v;
+        }
+        if !vertex.normal.near_zero() {
+            vertex.normal = vertex.normal.unit();
+        }
+
+        vertex
+    }
+}
+
+/// A triangle, shaded with the normals of its vertices interpolated across it.
+/// The geometric normal follows the winding, (b - a) x (c - a).
+#[derive(Clone)]
+pub struct Triangle {
+    pub vertices: [Vertex; 3],
+    pub material: Arc<MaterialSync>,
+    normal: Vec3,
+    dpdu: Vec3,
+    dpdv: Vec3,
+    bounding_box: AABB,
+    id: ObjectId
+}
+
+impl Triangle {
+    pub fn new(vertices: [Vertex; 3], material: Arc<MaterialSync>) -> Self {
+        let [a, b, c] = vertices;
+        let edge_ab = b.position - a.position;
+        let edge_ac = c.position - a.position;
+
+        // Solve the edges for the change of the point along u and v
+        let (du_ab, dv_ab) = (b.u - a.u, b.v - a.v);
+        let (du_ac, dv_ac) = (c.u - a.u, c.v - a.v);
+        let determinant = du_ab * dv_ac - dv_ab * du_ac;
+        let (dpdu, dpdv) = if determinant.abs() > 0.0 {
+            (
+                (dv_ac * edge_ab - dv_ab * edge_ac) / determinant,
+                (du_ab * edge_ac - du_ac * edge_ab) / determinant
+            )
+        } else {
+            // No usable texture coordinates, the tangent frame is arbitrary
+            (edge_ab, edge_ac)
+        };
+
+        Triangle {
+            vertices,
+            material,
+            normal: edge_ab.cross(edge_ac).unit(),
+            dpdu,
+            dpdv,
+            bounding_box: (AABB::new_from_points(a.position, b.position) + AABB::new_from_points(c.position, c.position)).pad(),
+            id: new_object_id()
+        }
+    }
+
+    /// Make the triangle part of a larger object, its hits report that object's ID
+    pub fn with_object_id(self, id: ObjectId) -> Self {
+        Triangle { id, ..self }
+    }
+
+    fn area(&self) -> f64 {
+        let [a, b, c] = self.vertices.map(|vertex| vertex.position);
+        0.5 * (b - a).cross(c - a).length()
+    }
+}
+
+impl Hittable for Triangle {
+    fn bounding_box(&self) -> AABB {
+        self.bounding_box
+    }
+
+    /// Möller-Trumbore: solve origin + t * direction = a + beta * (b - a) + gamma * (c - a)
+    /// for the distance and the barycentric coordinates.
+    fn hit(&self, ray: Ray, ray_interval: Interval) -> Option<HitRecord> {
+        let [a, b, c] = &self.vertices;
+        let edge_ab = b.position - a.position;
+        let edge_ac = c.position - a.position;
+
+        let p = ray.direction.cross(edge_ac);
+        let determinant = edge_ab.dot(p);
+        // The ray is parallel to the triangle
+        if determinant == 0.0 {
+            return None;
+        }
+        let inverse_determinant = 1.0 / determinant;
+
+        let to_origin = ray.origin - a.position;
+        let beta = to_origin.dot(p) * inverse_determinant;
+        if !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let q = to_origin.cross(edge_ab);
+        let gamma = ray.direction.dot(q) * inverse_determinant;
+        if gamma < 0.0 || beta + gamma > 1.0 {
+            return None;
+        }
+
+        let t = edge_ac.dot(q) * inverse_determinant;
+        if !ray_interval.contains(t) {
+            return None;
+        }
+
+        let point = ray.at(t);
+        let corner = Vertex::interpolate(&self.vertices, [1.0 - beta - gamma, beta, gamma]);
+        if !passes_alpha_test(&self.material, corner.u, corner.v, &point, &self.normal) {
+            return None;
+        }
+
+        let mut hitrec = HitRecord::new(
+            point,
+            self.normal,
+            self.material.clone(),
+            t,
+            corner.u,
+            corner.v,
+            ray
+        );
+        hitrec.set_uv_derivatives(self.dpdu, self.dpdv);
+        hitrec.object_id = self.id;
+
+        // Smooth shading, on the side the ray came from
+        if !corner.normal.near_zero() {
+            let side = if hitrec.front_face { 1.0 } else { -1.0 };
+            hitrec.set_shading_normal(side * corner.normal);
+        }
+
+        Some(hitrec)
+    }
+
+    fn emitters(&self) -> Vec<Arc<EmitterSync>> {
+        if self.light_bounds().power > 0.0 { vec![Arc::new(self.clone())] } else { Vec::new() }
+    }
+}
+
+impl UvSurface for Triangle {
+    /// Solve the texture coordinates of the corners for the barycentric coordinates of (u, v)
+    fn surface_at(&self, u: f64, v: f64) -> Option<HitRecord> {
+        let [a, b, c] = &self.vertices;
+        let (du_ab, dv_ab) = (b.u - a.u, b.v - a.v);
+        let (du_ac, dv_ac) = (c.u - a.u, c.v - a.v);
+        let determinant = du_ab * dv_ac - dv_ab * du_ac;
+        if determinant == 0.0 {
+            return None;
+        }
+
+        let (du, dv) = (u - a.u, v - a.v);
+        let beta = (du * dv_ac - dv * du_ac) / determinant;
+        let gamma = (du_ab * dv - dv_ab * du) / determinant;
+        if beta < 0.0 || gamma < 0.0 || beta + gamma > 1.0 {
+            return None;
+        }
+
+        let corner = Vertex::interpolate(&self.vertices, [1.0 - beta - gamma, beta, gamma]);
+        let mut hitrec = HitRecord::new_head_on(corner.position, self.normal, self.material.clone(), u, v);
+        hitrec.set_uv_derivatives(self.dpdu, self.dpdv);
+        hitrec.object_id = self.id;
+        if !corner.normal.near_zero() {
+            hitrec.set_shading_normal(corner.normal);
+        }
+
+        Some(hitrec)
+    }
+
+    fn material(&self) -> Arc<MaterialSync> {
+        self.material.clone()
+    }
+}
+
+impl Emitter for Triangle {
+    /// Sample the triangle's area uniformly
+    fn sample_point(&self, origin: Point3) -> Option<(Point3, f64)> {
+        // The square root spreads samples evenly over the area instead of bunching them at a corner
+        let root = random::<f64>().sqrt();
+        let (beta, gamma) = (root * (1.0 - random::<f64>()), root * random::<f64>());
+        let point = Vertex::interpolate(&self.vertices, [1.0 - beta - gamma, beta, gamma]).position;
+
+        let to_point = point - origin;
+        let distance_squared = to_point.length_squared();
+        let cos_light = self.normal.dot(to_point).abs() / distance_squared.sqrt();
+        if cos_light < NEAR_ZERO_THRESHOLD {
+            return None;
+        }
+
+        Some((point, distance_squared / (cos_light * self.area())))
+    }
+
+    fn light_bounds(&self) -> LightBounds {
+        // The centroid and points halfway between it and the corners
+        let points: Vec<(Point3, Vec3, f64, f64)> = [[2.0, 2.0, 2.0], [4.0, 1.0, 1.0], [1.0, 4.0, 1.0], [1.0, 1.0, 4.0]]
+            .into_iter()
+            .map(|weights| {
+                let vertex = Vertex::interpolate(&self.vertices, weights.map(|weight| weight / 6.0));
+                (vertex.position, self.normal, vertex.u, vertex.v)
+            })
+            .collect();
+        let (front, back) = emitted_luminance(&self.material, &points);
+
+        LightBounds::new_surface(self.bounding_box, self.normal, front, back, self.area())
+    }
+
+    fn object_id(&self) -> ObjectId {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::material::Lambertian;
+    use crate::math::vec3::Color;
+
+    fn triangle() -> Triangle {
+        let normal = Vec3::new(0.0, 0.0, 1.0);
+        Triangle::new(
+            [
+                Vertex::new(Point3::new(0.0, 0.0, 0.0), normal, 0.2, 0.1),
+                Vertex::new(Point3::new(2.0, 0.0, 0.0), normal, 0.8, 0.1),
+                Vertex::new(Point3::new(0.0, 3.0, 0.0), normal, 0.2, 0.9)
+            ],
+            Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+        )
+    }
+
+    #[test]
+    fn surface_at_inverts_the_texture_coordinates() {
+        let triangle = triangle();
+        let corner_b = triangle.surface_at(0.8, 0.1).unwrap();
+        assert!((corner_b.point - Point3::new(2.0, 0.0, 0.0)).near_zero());
+
+        let inside = triangle.surface_at(0.35, 0.5).unwrap();
+        assert!((inside.point - Point3::new(0.5, 1.5, 0.0)).near_zero());
+        assert!(inside.front_face);
+    }
+
+    #[test]
+    fn surface_at_is_none_outside_the_triangle() {
+        let triangle = triangle();
+        assert!(triangle.surface_at(0.1, 0.5).is_none());
+        assert!(triangle.surface_at(0.7, 0.8).is_none());
+    }
+}
@@ -0,0 +1,69 @@
+//! Alpha cutouts and transparency masks.
+//!
+//! Wraps any material with an opacity texture. Geometry asks the material for its
+//! opacity before accepting a hit, so rays and shadows pass through the masked
+//! parts, regardless of how deep in a BVH or Translate/RotateY the surface is.
+
+use std::sync::Arc;
+
+use crate::geometry::{Ray, hittable::HitRecord};
+use crate::math::vec3::{Color, Point3, Vec3};
+
+use super::{compositing::Compositing, material::{Material, MaterialSync}, texture::TextureSync};
+
+
+pub enum AlphaMode {
+    /// Binary cutout, the surface exists only where the mask reaches the threshold
+    Cutout { threshold: f64 },
+    /// The mask is the probability of a ray hitting the surface,
+    /// converges to partial transparency over many samples
+    Stochastic
+}
+
+pub struct AlphaMasked {
+    base: Arc<MaterialSync>,
+    mask: Arc<TextureSync>,
+    mode: AlphaMode
+}
+
+impl AlphaMasked {
+    pub fn new_cutout(base: Arc<MaterialSync>, mask: Arc<TextureSync>, threshold: f64) -> Self {
+        AlphaMasked { base, mask, mode: AlphaMode::Cutout { threshold } }
+    }
+
+    pub fn new_stochastic(base: Arc<MaterialSync>, mask: Arc<TextureSync>) -> Self {
+        AlphaMasked { base, mask, mode: AlphaMode::Stochastic }
+    }
+}
+
+impl Material for AlphaMasked {
+    fn scatter(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<(Color, Ray)> {
+        self.base.scatter(incident_ray, hitrec)
+    }
+
+    fn emitted(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Color {
+        self.base.emitted(incident_ray, hitrec)
+    }
+
+    fn eval(&self, incident_ray: &Ray, hitrec: &HitRecord, direction: Vec3) -> Option<Color> {
+        self.base.eval(incident_ray, hitrec, direction)
+    }
+
+    fn opacity(&self, u: f64, v: f64, point: &Point3, normal: &Vec3) -> f64 {
+        let alpha = self.mask.scalar_value(u, v, point, normal);
+        let mask_opacity = match self.mode {
+            AlphaMode::Cutout { threshold } => if alpha >= threshold { 1.0 } else { 0.0 },
+            AlphaMode::Stochastic => alpha.clamp(0.0, 1.0)
+        };
+
+        mask_opacity * self.base.opacity(u, v, point, normal)
+    }
+
+    fn compositing(&self, hitrec: &HitRecord) -> Compositing {
+        self.base.compositing(hitrec)
+    }
+
+    fn toon_shade(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<Color> {
+        self.base.toon_shade(incident_ray, hitrec)
+    }
+}
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use crate::graphics::aabb::AABB;
+use crate::graphics::{aabb::AABB, light_bvh::EmitterSync};
 use crate::math::interval::Interval;
 use crate::geometry::{
     Ray,
@@ -49,6 +49,16 @@ impl Hittable for BVH {
 
         bvh_hitrec
     }
+
+    fn emitters(&self) -> Vec<Arc<EmitterSync>> {
+        let mut emitters = self.left_node.emitters();
+        // Single object nodes hold it on both sides
+        if !Arc::ptr_eq(&self.left_node, &self.right_node) {
+            emitters.extend(self.right_node.emitters());
+        }
+
+        emitters
+    }
 }
 
 
@@ -68,7 +78,7 @@ impl BVH {
 
         // Build a bounding box surrounding the objects to find the longest axis
         let mut aux_bbox = AABB::new_empty();
-        for obj in objects.clone() {
+        for obj in &objects[start..end] {
             aux_bbox += obj.bounding_box()
         }
         
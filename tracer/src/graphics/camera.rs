@@ -4,11 +4,20 @@ use rand::random;
 
 use crate::math::{
     interval::Interval,
-    utils::random_in_unit_disk
+    spectrum::luminance,
+    utils::{random_in_unit_disk, get_random_point_in_unit_sphere}
 };
-use crate::geometry::{hittable::Hittable, Ray};
+use crate::geometry::{hittable::{Hittable, HitRecord}, Ray, RayDifferentials};
+use crate::graphics::{compositing::Compositing, light::DeltaLightSync, light_bvh::LightBVH, material::Lambertian, toon::Outline};
 use crate::math::vec3::{Point3, Vec3, Color};
-use crate::rendering::color::COLOR_BLACK;
+use crate::rendering::{bake::BakePass, color::{COLOR_BLACK, COLOR_WHITE}};
+
+/// How the camera turns the rays it shoots into colors.
+pub enum Integrator {
+    PathTraced,
+    /// Cel shading with outlines, materials without a toon shade are path traced
+    Toon(Outline)
+}
 
 pub struct Camera {
     center: Point3,
@@ -35,6 +44,10 @@ pub struct Camera {
     pub image_width: usize,
     pub image_height: usize,
     pub background: Color,
+    pub transparent_background: bool,   // camera rays that miss everything get zero alpha
+    pub integrator: Integrator,
+    pub lights: Vec<Arc<DeltaLightSync>>,   // lights rays can't hit, sampled directly at every bounce
+    pub light_tree: Option<LightBVH>,       // emissive surfaces, sampled directly at every bounce
 
     // Aux
     pub is_initialized: bool
@@ -42,6 +55,16 @@ pub struct Camera {
 
 pub const T_MIN_TOLERANCE: f64 = 0.001;
 
+/// Direct light reflected at a surface point, and what it would be if nothing cast a shadow
+struct DirectLight {
+    lit: Color,
+    unshadowed: Color
+}
+
+impl DirectLight {
+    const NONE: DirectLight = DirectLight { lit: COLOR_BLACK, unshadowed: COLOR_BLACK };
+}
+
 
 impl Default for Camera {
     fn default() -> Self {
@@ -64,6 +87,10 @@ impl Default for Camera {
             image_width: 640,
             image_height: 360,
             background: COLOR_BLACK,
+            transparent_background: false,
+            integrator: Integrator::PathTraced,
+            lights: Vec::new(),
+            light_tree: None,
             is_initialized: false
         }
     }
@@ -106,17 +133,23 @@ impl Camera {
         self.is_initialized = true;
     }
 
-    /// Return a ray's color on the viewport
+    /// Return a ray's color, premultiplied by its alpha, and alpha on the viewport
     pub fn render_ray(&self,
                       row: f64,
                       col: f64,
                       object: &Arc<dyn Hittable>,
-                      trace_depth: usize) -> Color {
+                      trace_depth: usize) -> (Color, f64) {
         if !self.is_initialized {
             panic!("Camera must be initialized before rendering.");
         }
-        let ray = self.generate_ray(row, col);
-        self.ray_color(ray, object, trace_depth)
+
+        match &self.integrator {
+            Integrator::PathTraced => {
+                let ray = self.generate_ray(row, col);
+                self.camera_ray_color(ray, object, trace_depth)
+            }
+            Integrator::Toon(outline) => self.toon_ray_color(row, col, object, trace_depth, outline)
+        }
     }
 
     /// Get a randomly-sampled camera ray for the pixel at location i,j, originating from
@@ -128,7 +161,26 @@ impl Camera {
         let ray_origin = self.sample_defocus_disk();
         let ray_direction = pixel_sample - ray_origin;
 
-        Ray::new(ray_origin, ray_direction, rand::random::<f64>())
+        // Offset rays through the same point on the lens, one pixel over
+        let differentials = RayDifferentials {
+            rx_origin: ray_origin,
+            rx_direction: ray_direction + self.pixel_delta_horizontal,
+            ry_origin: ray_origin,
+            ry_direction: ray_direction + self.pixel_delta_vertical
+        };
+
+        Ray {
+            differentials: Some(differentials),
+            ..Ray::new(ray_origin, ray_direction, rand::random::<f64>())
+        }
+    }
+
+    /// Get the ray from the camera center through a point on the viewport,
+    /// given in fractional pixel coordinates.
+    fn pinhole_ray(&self, row: f64, col: f64) -> Ray {
+        let viewport_point = self.pixel00_loc + (row * self.pixel_delta_vertical) + (col * self.pixel_delta_horizontal);
+
+        Ray::new(self.center, viewport_point - self.center, 0.0)
     }
 
     /// Return a random point in the square surrounding a pixel at the origin.
@@ -146,8 +198,126 @@ impl Camera {
         self.center + (p[0] * self.defocus_disk_horizontal) + (p[1] * self.defocus_disk_vertical)
     }
 
+    /// Render what the camera sees directly, taking compositing materials into account.
+    /// Return the color premultiplied by alpha, and the alpha.
+    fn camera_ray_color(&self, ray: Ray, object: &Arc<dyn Hittable>, trace_depth: usize) -> (Color, f64) {
+        if trace_depth == 0 {
+            return (COLOR_BLACK, 1.0);
+        }
+
+        let mut hitrec = match object.hit(ray, Interval::new(T_MIN_TOLERANCE, f64::INFINITY)) {
+            Some(hitrec) => hitrec,
+            None if self.transparent_background => return (COLOR_BLACK, 0.0),
+            None => return (self.background, 1.0)
+        };
+        hitrec.set_differentials(&ray);
+        pick_material(&mut hitrec);
+
+        self.camera_hit_color(ray, &hitrec, object, trace_depth)
+    }
+
+    /// Color and alpha of a hit seen directly by the camera, after its material is picked
+    fn camera_hit_color(&self, ray: Ray, hitrec: &HitRecord, object: &Arc<dyn Hittable>, trace_depth: usize) -> (Color, f64) {
+        match hitrec.material.compositing(hitrec) {
+            Compositing::Opaque => (self.shade(ray, hitrec, object, trace_depth, false), 1.0),
+            Compositing::Holdout => (COLOR_BLACK, 0.0),
+            Compositing::ShadowCatcher => self.shadow_catcher_color(ray, hitrec, object, trace_depth)
+        }
+    }
+
+    /// Cel shade what the camera sees directly, and draw outlines where the first hit
+    /// differs from the first hits of rays offset by the outline width.
+    /// Outlines are found through the pinhole, depth of field is ignored.
+    fn toon_ray_color(&self,
+                      row: f64,
+                      col: f64,
+                      object: &Arc<dyn Hittable>,
+                      trace_depth: usize,
+                      outline: &Outline) -> (Color, f64) {
+        let row = row + random::<f64>() - 0.5;
+        let col = col + random::<f64>() - 0.5;
+        let ray = self.pinhole_ray(row, col);
+        let ray_interval = Interval::new(T_MIN_TOLERANCE, f64::INFINITY);
+        let center_hit = object.hit(ray, ray_interval);
+
+        let offsets = [(-outline.width, 0.0), (outline.width, 0.0), (0.0, -outline.width), (0.0, outline.width)];
+        let is_edge = offsets.iter().any(|(row_offset, col_offset)| {
+            let neighbour_hit = object.hit(self.pinhole_ray(row + row_offset, col + col_offset), ray_interval);
+            outline.is_edge(self.center, &center_hit, &neighbour_hit)
+        });
+        if is_edge {
+            return (outline.color, 1.0);
+        }
+
+        let mut hitrec = match center_hit {
+            Some(hitrec) if trace_depth > 0 => hitrec,
+            _ => return self.camera_ray_color(ray, object, trace_depth)
+        };
+        hitrec.set_differentials(&ray);
+        pick_material(&mut hitrec);
+
+        // Path trace the same pick when it has no toon shade, rather than picking again
+        match hitrec.material.toon_shade(&ray, &hitrec) {
+            Some(color) => (color, 1.0),
+            None => self.camera_hit_color(ray, &hitrec, object, trace_depth)
+        }
+    }
+
+    /// A shadow catcher is only as opaque as it is in shadow: the fraction of the direct light
+    /// reaching it that scene geometry blocks, with the same light samples as shade().
+    /// The photograph already has the light itself, so the shadow is recorded as black,
+    /// and only the light the rendered objects reflect onto the catcher is added on top.
+    fn shadow_catcher_color(&self,
+                            ray: Ray,
+                            hitrec: &HitRecord,
+                            object: &Arc<dyn Hittable>,
+                            trace_depth: usize) -> (Color, f64) {
+        let from_lights = self.sample_delta_lights(&ray, hitrec, object);
+        let from_emitters = self.sample_emitters(&ray, hitrec, object, true)
+            .unwrap_or(DirectLight::NONE);
+
+        let unshadowed = luminance(from_lights.unshadowed + from_emitters.unshadowed);
+        let alpha = if unshadowed > 0.0 {
+            let lit = luminance(from_lights.lit + from_emitters.lit);
+            (1.0 - lit / unshadowed).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        (self.shadow_catcher_reflection(ray, hitrec, object, trace_depth), alpha)
+    }
+
+    /// Light bounced onto a shadow catcher by the rendered objects. Premultiplied, it adds
+    /// to the photograph whatever the alpha, like a reflection does.
+    /// Bounces that reach the background or another compositing surface see the photograph,
+    /// which already has that light.
+    fn shadow_catcher_reflection(&self,
+                                 ray: Ray,
+                                 hitrec: &HitRecord,
+                                 object: &Arc<dyn Hittable>,
+                                 trace_depth: usize) -> Color {
+        if trace_depth <= 1 {
+            return COLOR_BLACK;
+        }
+        let Some((attenuation, scattered)) = hitrec.material.scatter(&ray, hitrec) else {
+            return COLOR_BLACK;
+        };
+        let Some(mut bounce) = object.hit(scattered, Interval::new(T_MIN_TOLERANCE, f64::INFINITY)) else {
+            return COLOR_BLACK;
+        };
+        bounce.set_differentials(&scattered);
+        pick_material(&mut bounce);
+
+        match bounce.material.compositing(&bounce) {
+            Compositing::Opaque => attenuation * self.shade(scattered, &bounce, object, trace_depth - 1, false),
+            Compositing::Holdout | Compositing::ShadowCatcher => COLOR_BLACK
+        }
+    }
+
     /// Render the color of a single ray shot into the world.
-    fn ray_color(&self, ray: Ray, object: &Arc<dyn Hittable>, trace_depth: usize) -> Color {
+    /// emitters_sampled tells the ray comes from a bounce that already sampled the emitters
+    /// directly, so the emission it hits on emitters in the light tree was accounted for.
+    fn ray_color(&self, ray: Ray, object: &Arc<dyn Hittable>, trace_depth: usize, emitters_sampled: bool) -> Color {
         if trace_depth <= 0 { 
             return COLOR_BLACK;
         }
@@ -158,10 +328,27 @@ impl Camera {
         if hitrec_result.is_none() {
             return self.background;
         }
-        let hitrec = hitrec_result.unwrap();
+        let mut hitrec = hitrec_result.unwrap();
+        hitrec.set_differentials(&ray);
+
+        self.shade(ray, &hitrec, object, trace_depth, emitters_sampled)
+    }
 
-        let color_from_emission = hitrec.material.emitted(hitrec.u, hitrec.v, &hitrec.point);
-        let scatter_result = hitrec.material.scatter(&ray, &hitrec);
+    /// Color of a ray at the surface it hit.
+    fn shade(&self,
+             ray: Ray,
+             hitrec: &HitRecord,
+             object: &Arc<dyn Hittable>,
+             trace_depth: usize,
+             emitters_sampled: bool) -> Color {
+        // Emitters in the light tree were already sampled directly by the previous bounce
+        let in_light_tree = self.light_tree.as_ref().is_some_and(|tree| tree.contains(hitrec.object_id));
+        let color_from_emission = if emitters_sampled && in_light_tree {
+            COLOR_BLACK
+        } else {
+            hitrec.material.emitted(&ray, hitrec)
+        };
+        let scatter_result = hitrec.material.scatter(&ray, hitrec);
 
         // Regular materials return a result
         // Emissive materials return None from their scatter() method, so here we return the 
@@ -171,9 +358,130 @@ impl Camera {
         }
 
         let (attenuation, scattered) = scatter_result.unwrap();
-        let color_from_scatter = attenuation * self.ray_color(scattered, object, trace_depth - 1);
+        let color_from_lights = self.sample_delta_lights(&ray, hitrec, object).lit;
+        let color_from_emitters = self.sample_emitters(&ray, hitrec, object, false).map(|direct| direct.lit);
+        let color_from_scatter = attenuation * self.ray_color(
+            scattered,
+            object,
+            trace_depth - 1,
+            color_from_emitters.is_some()
+        );
 
         // ??? why add the emission color?
-        color_from_emission + color_from_scatter
+        color_from_emission + color_from_lights + color_from_emitters.unwrap_or(COLOR_BLACK) + color_from_scatter
+    }
+
+    /// A single sample of a bake pass at a surface point, see BakePass
+    pub fn bake_sample(&self,
+                       hitrec: &HitRecord,
+                       pass: &BakePass,
+                       object: &Arc<dyn Hittable>,
+                       trace_depth: usize) -> Color {
+        // Looking straight at the surface from the outside
+        let view_ray = Ray::new(hitrec.point + hitrec.normal, -hitrec.normal, 0.0);
+
+        match pass {
+            BakePass::AmbientOcclusion { distance } => {
+                // Cosine weighted direction over the hemisphere
+                let direction = hitrec.normal + get_random_point_in_unit_sphere().unit();
+                if direction.near_zero() {
+                    return COLOR_WHITE;
+                }
+
+                let occlusion_ray = view_ray.spawn(hitrec.point, direction.unit());
+                match object.hit(occlusion_ray, Interval::new(T_MIN_TOLERANCE, *distance)) {
+                    Some(_) => COLOR_BLACK,
+                    None => COLOR_WHITE
+                }
+            }
+            BakePass::Irradiance => {
+                let mut white_surface = hitrec.clone();
+                white_surface.material = Arc::new(Lambertian::new(COLOR_WHITE));
+
+                self.shade(view_ray, &white_surface, object, trace_depth, false)
+            }
+            BakePass::Combined => self.shade(view_ray, hitrec, object, trace_depth, false)
+        }
     }
-}
\ No newline at end of file
+
+    /// Light arriving from an emitter picked by the light tree, with a single shadow ray.
+    /// None if there is no light tree or the material can't be evaluated, emitters are
+    /// then only found by scattering into them.
+    /// The light occluders would let through is only traced when see_past_occluders is set,
+    /// it takes a hit per occluder.
+    fn sample_emitters(&self,
+                       ray: &Ray,
+                       hitrec: &HitRecord,
+                       object: &Arc<dyn Hittable>,
+                       see_past_occluders: bool) -> Option<DirectLight> {
+        let light_tree = self.light_tree.as_ref()?;
+        hitrec.material.eval(ray, hitrec, hitrec.normal)?;
+
+        let Some((emitter, pick_probability)) = light_tree.sample(hitrec.point) else {
+            return Some(DirectLight::NONE);
+        };
+        let Some((light_point, pdf)) = emitter.sample_point(hitrec.point) else {
+            return Some(DirectLight::NONE);
+        };
+
+        let to_light = light_point - hitrec.point;
+        let distance = to_light.length();
+        let direction = to_light / distance;
+        let reflected = hitrec.material.eval(ray, hitrec, direction)?;
+        if reflected.near_zero() || pdf <= 0.0 {
+            return Some(DirectLight::NONE);
+        }
+
+        // The first thing along the shadow ray has to be the sampled point
+        let shadow_ray = ray.spawn(hitrec.point, direction);
+        let mut t_min = T_MIN_TOLERANCE;
+        let mut occluded = false;
+        while let Some(light_hit) = object.hit(shadow_ray, Interval::new(t_min, distance + T_MIN_TOLERANCE)) {
+            if light_hit.t >= distance - T_MIN_TOLERANCE {
+                let emitted = light_hit.material.emitted(&shadow_ray, &light_hit);
+                let color = reflected * emitted / (pick_probability * pdf);
+                return Some(DirectLight { lit: if occluded { COLOR_BLACK } else { color }, unshadowed: color });
+            }
+            if !see_past_occluders {
+                break;
+            }
+            occluded = true;
+            t_min = light_hit.t + T_MIN_TOLERANCE;
+        }
+
+        Some(DirectLight::NONE)
+    }
+
+    /// Light arriving straight from the lights rays can't hit, with a shadow ray per light.
+    /// Only materials that can be evaluated in a given direction are lit by them.
+    fn sample_delta_lights(&self, ray: &Ray, hitrec: &HitRecord, object: &Arc<dyn Hittable>) -> DirectLight {
+        let mut direct = DirectLight::NONE;
+
+        for light in &self.lights {
+            let Some(sample) = light.sample(hitrec.point) else {
+                continue;
+            };
+            let Some(reflected) = hitrec.material.eval(ray, hitrec, sample.direction) else {
+                return DirectLight::NONE;
+            };
+            if reflected.near_zero() {
+                continue;
+            }
+
+            direct.unshadowed += reflected * sample.radiance;
+            let shadow_ray = ray.spawn(hitrec.point, sample.direction);
+            if object.hit(shadow_ray, Interval::new(T_MIN_TOLERANCE, sample.distance)).is_none() {
+                direct.lit += reflected * sample.radiance;
+            }
+        }
+
+        direct
+    }
+}
+
+/// Replace materials that pick between others with their pick, down to one that doesn't
+fn pick_material(hitrec: &mut HitRecord) {
+    while let Some(material) = hitrec.material.pick(hitrec) {
+        hitrec.material = material;
+    }
+}
@@ -0,0 +1,80 @@
+//! Materials for compositing renders over photographs.
+//!
+//! A holdout cuts a transparent hole in the image wherever the camera sees it.
+//! A shadow catcher stands in for a surface of the photograph (usually the ground):
+//! it is transparent by itself, and records the shadows that the rendered objects
+//! cast onto it from the lights that are sampled directly, as alpha over black,
+//! plus the light the rendered objects reflect onto it, as premultiplied color.
+//!
+//! Both only have an effect on what the camera sees directly. Seen from anywhere else,
+//! a holdout is black and a shadow catcher is diffuse.
+
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::geometry::{Ray, hittable::HitRecord};
+use crate::math::{utils::get_random_point_on_unit_sphere, vec3::{Color, Vec3}};
+
+use super::{material::Material, texture::{TextureSync, SolidColorTexture}};
+
+
+/// How the camera writes a material's hits into the alpha channel.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Compositing {
+    Opaque,
+    Holdout,
+    ShadowCatcher
+}
+
+
+// Holdout
+// =======
+pub struct Holdout;
+
+impl Material for Holdout {
+    fn scatter(&self, _incident_ray: &Ray, _hitrec: &HitRecord) -> Option<(Color, Ray)> {
+        None
+    }
+
+    fn compositing(&self, _hitrec: &HitRecord) -> Compositing {
+        Compositing::Holdout
+    }
+}
+
+
+// Shadow catcher
+// ==============
+/// The albedo should roughly match the photographed surface, it tints the light
+/// bounced from the catcher onto the rendered objects.
+pub struct ShadowCatcher {
+    albedo: Arc<TextureSync>
+}
+
+impl ShadowCatcher {
+    pub fn new(albedo: Color) -> Self {
+        ShadowCatcher { albedo: Arc::new(SolidColorTexture::new(albedo)) }
+    }
+}
+
+impl Material for ShadowCatcher {
+    fn scatter(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<(Color, Ray)> {
+        let mut scatter_direction = hitrec.normal + get_random_point_on_unit_sphere();
+        if scatter_direction.near_zero() { scatter_direction = hitrec.normal; }
+
+        Some((
+            self.albedo.value(hitrec.u, hitrec.v, &hitrec.point, &hitrec.geometric_normal),
+            incident_ray.spawn(hitrec.point, scatter_direction)
+        ))
+    }
+
+    /// Diffuse, so the lights sampled directly can be checked for shadows
+    fn eval(&self, _incident_ray: &Ray, hitrec: &HitRecord, direction: Vec3) -> Option<Color> {
+        let cos_theta = hitrec.normal.dot(direction).max(0.0);
+
+        Some(self.albedo.value(hitrec.u, hitrec.v, &hitrec.point, &hitrec.geometric_normal) * cos_theta / PI)
+    }
+
+    fn compositing(&self, _hitrec: &HitRecord) -> Compositing {
+        Compositing::ShadowCatcher
+    }
+}
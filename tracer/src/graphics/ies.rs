@@ -0,0 +1,131 @@
+//! IES LM-63 photometric profiles.
+//!
+//! A profile tabulates a fixture's luminous intensity in candela over a grid of
+//! vertical and horizontal angles. Only type C photometry is supported (by far the
+//! most common): vertical angles go from 0 at the nadir, straight down the fixture's
+//! axis, to 180 straight up, and horizontal angles go around the axis.
+
+use std::fs;
+use std::io::{Error, ErrorKind};
+
+use crate::math::vec3::Vec3;
+
+const PHOTOMETRIC_TYPE_C: i32 = 1;
+
+
+pub struct IesProfile {
+    vertical_angles: Vec<f64>,      // degrees, ascending
+    horizontal_angles: Vec<f64>,    // degrees, ascending
+    candela: Vec<f64>               // for each horizontal angle, the values for all vertical angles
+}
+
+impl IesProfile {
+    pub fn load(path: &str) -> std::io::Result<Self> {
+        let text = fs::read_to_string(path).map_err(|err| Error::new(err.kind(), format!("{path}: {err}")))?;
+
+        IesProfile::parse(&text).map_err(|message| Error::new(ErrorKind::InvalidData, format!("{path}: {message}")))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        // Keywords come first, the numbers start right after the TILT line
+        let mut lines = text.lines();
+        let tilt = lines
+            .by_ref()
+            .find_map(|line| line.trim().strip_prefix("TILT="))
+            .ok_or("missing TILT line")?
+            .trim()
+            .to_string();
+
+        let mut numbers = lines
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|token| !token.is_empty())
+            .map(|token| token.parse::<f64>().map_err(|_| format!("invalid number '{token}'")));
+        let mut next = || numbers.next().unwrap_or(Err("unexpected end of data".to_string()));
+
+        if tilt == "INCLUDE" {
+            // Lamp to luminaire geometry, then pairs of tilt angles and factors. Tilt is ignored.
+            next()?;
+            let pairs = next()? as usize;
+            for _ in 0..2 * pairs {
+                next()?;
+            }
+        }
+
+        let _lamp_count = next()?;
+        let _lumens_per_lamp = next()?;
+        let multiplier = next()?;
+        let vertical_count = next()? as usize;
+        let horizontal_count = next()? as usize;
+        let photometric_type = next()? as i32;
+        let _units = next()?;
+        let _dimensions = (next()?, next()?, next()?);
+        let ballast_factor = next()?;
+        let _ballast_lamp_factor = next()?;
+        let _input_watts = next()?;
+
+        if photometric_type != PHOTOMETRIC_TYPE_C {
+            return Err(format!("unsupported photometric type {photometric_type}, only type C is"));
+        }
+        if vertical_count == 0 || horizontal_count == 0 {
+            return Err("empty candela table".to_string());
+        }
+
+        let vertical_angles = (0..vertical_count).map(|_| next()).collect::<Result<Vec<f64>, String>>()?;
+        let horizontal_angles = (0..horizontal_count).map(|_| next()).collect::<Result<Vec<f64>, String>>()?;
+        let candela = (0..vertical_count * horizontal_count)
+            .map(|_| next().map(|value| value * multiplier * ballast_factor))
+            .collect::<Result<Vec<f64>, String>>()?;
+
+        Ok(IesProfile { vertical_angles, horizontal_angles, candela })
+    }
+
+    /// Luminous intensity in candela towards a direction given in the fixture's frame,
+    /// where -Z is the nadir and X is at horizontal angle 0.
+    pub fn candela(&self, direction: Vec3) -> f64 {
+        let direction = direction.unit();
+        let vertical = (-direction.z()).clamp(-1.0, 1.0).acos().to_degrees();
+        let horizontal = direction.y().atan2(direction.x()).to_degrees().rem_euclid(360.0);
+
+        // The last horizontal angle tells which symmetry the table relies on
+        let last = *self.horizontal_angles.last().unwrap();
+        let horizontal = if last == 0.0 {
+            0.0
+        } else if last == 90.0 {
+            let half = if horizontal > 180.0 { 360.0 - horizontal } else { horizontal };
+            if half > 90.0 { 180.0 - half } else { half }
+        } else if last == 180.0 && horizontal > 180.0 {
+            360.0 - horizontal
+        } else {
+            horizontal
+        };
+
+        let Some((v0, v1, vt)) = bracket(&self.vertical_angles, vertical) else {
+            return 0.0;
+        };
+        let (h0, h1, ht) = bracket(&self.horizontal_angles, horizontal).unwrap_or((0, 0, 0.0));
+
+        let value = |h: usize, v: usize| self.candela[h * self.vertical_angles.len() + v];
+        let lower = value(h0, v0) * (1.0 - vt) + value(h0, v1) * vt;
+        let upper = value(h1, v0) * (1.0 - vt) + value(h1, v1) * vt;
+
+        lower * (1.0 - ht) + upper * ht
+    }
+}
+
+/// Find the two angles around a given one and how far between them it is.
+/// None if the angle is outside the table.
+fn bracket(angles: &[f64], angle: f64) -> Option<(usize, usize, f64)> {
+    if angles.len() == 1 {
+        return (angle == angles[0]).then_some((0, 0, 0.0));
+    }
+    if angle < angles[0] || angle > angles[angles.len() - 1] {
+        return None;
+    }
+
+    let upper = angles.partition_point(|&value| value < angle).clamp(1, angles.len() - 1);
+    let lower = upper - 1;
+    let span = angles[upper] - angles[lower];
+    let t = if span > 0.0 { (angle - angles[lower]) / span } else { 0.0 };
+
+    Some((lower, upper, t))
+}
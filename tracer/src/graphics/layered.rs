@@ -0,0 +1,289 @@
+//! Layered materials.
+//!
+//! Clearcoat is a thin dielectric coat over any other material, like car paint,
+//! varnished wood or glazed ceramic. The energy exchange between the layers is simulated
+//! stochastically: every scatter follows a single random path into the coat, off the
+//! base, and possibly back and forth between the base and the top of the coat.
+//!
+//! ThinFilm is a coat thin enough for the light reflecting off its top and bottom to
+//! interfere, like soap bubbles, oil slicks and anodized metals.
+
+use std::sync::Arc;
+
+use rand::random;
+
+use crate::geometry::{Ray, hittable::HitRecord};
+use crate::math::{
+    consts::NEAR_ZERO_THRESHOLD,
+    optics::{reflect, refract, reflectance, fresnel_amplitudes, airy_reflectance},
+    utils::get_random_point_in_unit_sphere,
+    vec3::{Color, Point3, Vec3}
+};
+use crate::rendering::color::COLOR_WHITE;
+
+use super::{
+    compositing::Compositing,
+    material::{Material, MaterialSync},
+    medium::OUTSIDE_IOR,
+    texture::{TextureSync, SolidColorTexture}
+};
+
+/// Bounces inside the coat after which the path is considered absorbed
+const MAX_INTERNAL_BOUNCES: usize = 8;
+
+/// Scatters off a thin film's base after which it is taken not to refract any light
+const MAX_REFRACTION_TRIES: usize = 16;
+
+/// Wavelengths in nanometers standing in for the red, green and blue channels
+const RGB_WAVELENGTHS: [f64; 3] = [650.0, 550.0, 450.0];
+
+
+pub struct Clearcoat {
+    base: Arc<MaterialSync>,
+    ior: f64,           // index of refraction of the coat
+    roughness: f64,     // fuzz of the reflection off the coat, 0 is a perfect mirror
+    thickness: f64,     // thickness of the coat, in units of the absorption tint
+    absorption: Color   // color transmitted through a coat of thickness 1 at normal incidence
+}
+
+impl Clearcoat {
+    pub fn new(base: Arc<MaterialSync>, ior: f64, roughness: f64, thickness: f64, absorption: Color) -> Self {
+        Clearcoat { base, ior, roughness, thickness, absorption }
+    }
+
+    /// A clear coat that doesn't absorb any light
+    pub fn new_clear(base: Arc<MaterialSync>, ior: f64, roughness: f64) -> Self {
+        Clearcoat { base, ior, roughness, thickness: 0.0, absorption: COLOR_WHITE }
+    }
+
+    /// Beer-Lambert transmittance through the coat along a direction,
+    /// cosine is measured against the surface normal.
+    fn transmittance(&self, cosine: f64) -> Color {
+        let path_length = self.thickness / cosine.max(NEAR_ZERO_THRESHOLD);
+
+        Color::new(
+            self.absorption.x().powf(path_length),
+            self.absorption.y().powf(path_length),
+            self.absorption.z().powf(path_length)
+        )
+    }
+}
+
+impl Material for Clearcoat {
+    fn scatter(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<(Color, Ray)> {
+        // Rays coming from inside the object never see the coat
+        if !hitrec.front_face {
+            return self.base.scatter(incident_ray, hitrec);
+        }
+
+        let normal = hitrec.normal;
+        let unit_direction = incident_ray.direction.unit();
+        let cos_incident = -unit_direction.dot(normal).min(1.0);
+        // Relative index of refraction from the medium the ray travels in into the coat
+        let eta = incident_ray.media.top().map_or(OUTSIDE_IOR, |medium| medium.ior) / self.ior;
+
+        // Reflect off the top of the coat
+        if reflectance(cos_incident, eta) > random::<f64>() {
+            let mirrored = reflect(unit_direction, normal);
+            let mut reflected = mirrored + self.roughness * get_random_point_in_unit_sphere();
+            if reflected.dot(hitrec.geometric_normal) <= 0.0 { reflected = mirrored; }
+
+            return Some((COLOR_WHITE, incident_ray.spawn(hitrec.point, reflected)));
+        }
+
+        // Refract into the coat, then bounce between the base and the top of the coat
+        let mut direction = refract(unit_direction, normal, eta);
+        let mut attenuation = self.transmittance(-direction.dot(normal));
+
+        for _ in 0..MAX_INTERNAL_BOUNCES {
+            // The base sees light arriving through the coat
+            let coat_ray = incident_ray.spawn(hitrec.point - direction, direction);
+            let (base_attenuation, base_scattered) = self.base.scatter(&coat_ray, hitrec)?;
+            attenuation = attenuation * base_attenuation;
+
+            // Transmissive bases send light into the object, the coat isn't involved
+            let up_direction = base_scattered.direction.unit();
+            let cos_up = up_direction.dot(normal);
+            if cos_up <= 0.0 {
+                return Some((attenuation, base_scattered));
+            }
+            attenuation = attenuation * self.transmittance(cos_up);
+
+            // Leave through the top of the coat, or get reflected back down to the base
+            let sin_up = (1.0 - cos_up.powi(2)).sqrt();
+            let cannot_refract = sin_up / eta > 1.0;
+            if !cannot_refract && reflectance(cos_up, 1.0 / eta) <= random::<f64>() {
+                let exit_direction = refract(up_direction, -normal, 1.0 / eta);
+                return Some((attenuation, base_scattered.spawn(hitrec.point, exit_direction)));
+            }
+
+            direction = reflect(up_direction, -normal);
+            attenuation = attenuation * self.transmittance(cos_up);
+        }
+
+        None
+    }
+
+    fn emitted(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Color {
+        self.base.emitted(incident_ray, hitrec)
+    }
+
+    fn opacity(&self, u: f64, v: f64, point: &Point3, normal: &Vec3) -> f64 {
+        self.base.opacity(u, v, point, normal)
+    }
+
+    fn compositing(&self, hitrec: &HitRecord) -> Compositing {
+        self.base.compositing(hitrec)
+    }
+
+    /// The base's flat shading, cel shading has no use for the coat's reflection
+    fn toon_shade(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<Color> {
+        self.base.toon_shade(incident_ray, hitrec)
+    }
+}
+
+
+// Thin film
+// =========
+
+/// What is under a thin film
+pub enum FilmSubstrate {
+    /// Light going through the film is refracted by the base material, a Dielectric of
+    /// the same index. The film's reflectance already has the reflection off the substrate
+    /// in it, so the base's own reflection is left out.
+    Dielectric { ior: f64 },
+    /// Light going through the film is absorbed, like a Metal.
+    /// reflectance is the substrate's color at normal incidence,
+    /// the base only decides the direction of the reflection.
+    Conductor { reflectance: Color }
+}
+
+pub struct ThinFilm {
+    base: Arc<MaterialSync>,
+    substrate: FilmSubstrate,
+    film_ior: f64,
+    thickness_nm: f64,              // thickness of the film, in nanometers
+    thickness: Arc<TextureSync>     // scales thickness_nm across the surface
+}
+
+impl ThinFilm {
+    pub fn new(base: Arc<MaterialSync>, substrate: FilmSubstrate, film_ior: f64, thickness_nm: f64) -> Self {
+        ThinFilm {
+            base,
+            substrate,
+            film_ior,
+            thickness_nm,
+            thickness: Arc::new(SolidColorTexture::new(COLOR_WHITE))
+        }
+    }
+
+    pub fn new_texture(base: Arc<MaterialSync>,
+                       substrate: FilmSubstrate,
+                       film_ior: f64,
+                       thickness_nm: f64,
+                       thickness: Arc<TextureSync>) -> Self {
+        ThinFilm { base, substrate, film_ior, thickness_nm, thickness }
+    }
+
+    /// Reflectance of the film per channel, for light coming from a medium with
+    /// index of refraction outside_ior at an angle with the given cosine.
+    fn reflectance(&self, cos_incident: f64, outside_ior: f64, thickness_nm: f64) -> Color {
+        // Snell's law into the film
+        let sin2_incident = 1.0 - cos_incident * cos_incident;
+        let sin2_film = (outside_ior / self.film_ior).powi(2) * sin2_incident;
+        if sin2_film >= 1.0 {
+            return COLOR_WHITE;
+        }
+        let cos_film = (1.0 - sin2_film).sqrt();
+        let (r01_s, r01_p) = fresnel_amplitudes(outside_ior, cos_incident, self.film_ior, cos_film);
+
+        let mut reflectance = Color::zero();
+        for (channel, wavelength) in RGB_WAVELENGTHS.iter().enumerate() {
+            let (r12_s, r12_p) = match self.substrate {
+                FilmSubstrate::Dielectric { ior } => {
+                    let sin2_substrate = (outside_ior / ior).powi(2) * sin2_incident;
+                    if sin2_substrate >= 1.0 {
+                        (1.0, 1.0)
+                    } else {
+                        fresnel_amplitudes(self.film_ior, cos_film, ior, (1.0 - sin2_substrate).sqrt())
+                    }
+                }
+                // Conductors flip the phase, the magnitude is Schlick's approximation
+                FilmSubstrate::Conductor { reflectance } => {
+                    let f0 = reflectance[channel];
+                    let amplitude = -(f0 + (1.0 - f0) * (1.0 - cos_film).powi(5)).sqrt();
+                    (amplitude, amplitude)
+                }
+            };
+
+            // Optical path difference between consecutive rays leaving the film
+            let phase = 4.0 * std::f64::consts::PI * self.film_ior * thickness_nm * cos_film / wavelength;
+
+            reflectance[channel] = 0.5 * (
+                airy_reflectance(r01_s, r12_s, phase) + airy_reflectance(r01_p, r12_p, phase)
+            );
+        }
+
+        reflectance
+    }
+}
+
+impl Material for ThinFilm {
+    fn scatter(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<(Color, Ray)> {
+        // Rays coming from inside the object never see the film
+        if !hitrec.front_face {
+            return self.base.scatter(incident_ray, hitrec);
+        }
+
+        let unit_direction = incident_ray.direction.unit();
+        let cos_incident = -unit_direction.dot(hitrec.normal).min(1.0);
+        let outside_ior = incident_ray.media.top().map_or(OUTSIDE_IOR, |medium| medium.ior);
+        let thickness_nm = self.thickness_nm * self.thickness.scalar_value(hitrec.u, hitrec.v, &hitrec.point, &hitrec.geometric_normal);
+        let reflectance = self.reflectance(cos_incident, outside_ior, thickness_nm);
+
+        match self.substrate {
+            FilmSubstrate::Conductor { .. } => {
+                let (_, scattered) = self.base.scatter(incident_ray, hitrec)?;
+                Some((reflectance, scattered))
+            }
+            FilmSubstrate::Dielectric { .. } => {
+                // Pick reflecting off the film or going through it by the average reflectance,
+                // and weight the channels so it averages out to the actual colors.
+                let reflect_probability = reflectance.dot(COLOR_WHITE) / 3.0;
+
+                if random::<f64>() < reflect_probability {
+                    let reflected = incident_ray.spawn(hitrec.point, reflect(unit_direction, hitrec.normal));
+                    return Some((reflectance / reflect_probability, reflected));
+                }
+
+                // Only the light the base refracts, its reflection is part of the film's
+                let transmittance = (COLOR_WHITE - reflectance) / (1.0 - reflect_probability);
+                for _ in 0..MAX_REFRACTION_TRIES {
+                    let (attenuation, scattered) = self.base.scatter(incident_ray, hitrec)?;
+                    if scattered.direction.dot(hitrec.geometric_normal) < 0.0 {
+                        return Some((attenuation * transmittance, scattered));
+                    }
+                }
+
+                None
+            }
+        }
+    }
+
+    fn emitted(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Color {
+        self.base.emitted(incident_ray, hitrec)
+    }
+
+    fn opacity(&self, u: f64, v: f64, point: &Point3, normal: &Vec3) -> f64 {
+        self.base.opacity(u, v, point, normal)
+    }
+
+    fn compositing(&self, hitrec: &HitRecord) -> Compositing {
+        self.base.compositing(hitrec)
+    }
+
+    /// The base's flat shading, without the film's colors
+    fn toon_shade(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<Color> {
+        self.base.toon_shade(incident_ray, hitrec)
+    }
+}
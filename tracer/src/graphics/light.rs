@@ -1,9 +1,13 @@
+use std::f64::consts::PI;
 use std::sync::Arc;
 
 use crate::{Point3, Color};
 use crate::geometry::{Ray, hittable::HitRecord};
+use crate::math::{spectrum::{blackbody, luminance, LUMINOUS_EFFICACY}, utils::orthonormal_basis, vec3::Vec3};
+use crate::rendering::color::COLOR_BLACK;
 
 use super::{
+    ies::IesProfile,
     texture::{TextureSync, SolidColorTexture},
     material::Material
 };
@@ -26,20 +30,45 @@ impl Light {
 
 // ==============================
 
+/// Emitters set up with physical units take scene units to be meters, and a color
+/// of luminance 1 to be a radiance of 1 W/(sr*m^2).
 pub struct DiffuseLight {
-    emit: Arc<TextureSync>
+    emit: Arc<TextureSync>,
+    one_sided: bool     // only emit on the side the normal points to
 }
 
 impl DiffuseLight {
     pub fn new(texture: Arc<TextureSync>) -> Self {
-        DiffuseLight { emit: texture }
+        DiffuseLight { emit: texture, one_sided: false }
     }
 
     pub fn new_color(color: Color) -> Self {
-        DiffuseLight { emit: Arc::new(SolidColorTexture::new(color)) }
+        DiffuseLight { emit: Arc::new(SolidColorTexture::new(color)), one_sided: false }
     }
 
+    /// Black body color at the given temperature, scaled to the given luminance
+    pub fn new_blackbody(kelvin: f64, luminance: f64) -> Self {
+        DiffuseLight::new_color(blackbody(kelvin) * luminance)
+    }
+
+    /// Emit a total radiant power in watts, spread evenly over the emitter's area.
+    /// Only the hue of the color is used, its brightness is normalized away, e.g. DiffuseLight::new_watts(blackbody(3200.0), ...).
+    /// A black color has no hue to normalize, and gives a light that emits nothing.
+    pub fn new_watts(color: Color, watts: f64, area: f64, one_sided: bool) -> Self {
+        // A Lambertian emitter of radiance L emits L * PI * area watts from each side
+        let sides = if one_sided { 1.0 } else { 2.0 };
+        let radiance = watts / (PI * area * sides);
+        let color_luminance = luminance(color);
+        let emit = if color_luminance > 0.0 { color / color_luminance * radiance } else { COLOR_BLACK };
+
+        DiffuseLight { emit: Arc::new(SolidColorTexture::new(emit)), one_sided }
+    }
 
+    /// Emit a total luminous flux in lumens, spread evenly over the emitter's area.
+    /// A 60W incandescent bulb is around 800lm.
+    pub fn new_lumens(color: Color, lumens: f64, area: f64, one_sided: bool) -> Self {
+        DiffuseLight::new_watts(color, lumens / LUMINOUS_EFFICACY, area, one_sided)
+    }
 }
 
 impl Material for DiffuseLight {
@@ -47,7 +76,198 @@ impl Material for DiffuseLight {
         None
     }
 
-    fn emitted(&self, u: f64, v: f64, point: &Point3) -> Color {
-        self.emit.value(u, v, point)
+    fn emitted(&self, _incident_ray: &Ray, hitrec: &HitRecord) -> Color {
+        if self.one_sided && !hitrec.front_face {
+            return COLOR_BLACK;
+        }
+
+        self.emit.value(hitrec.u, hitrec.v, &hitrec.point, &hitrec.geometric_normal)
+    }
+}
+
+
+// Delta lights
+// ============
+/// Light arriving at a point from a light that can't be hit by rays, sampled directly
+/// by the integrator instead.
+pub struct LightSample {
+    pub direction: Vec3,    // unit vector from the point towards the light
+    pub distance: f64,
+    pub radiance: Color     // arriving at the point, falloff included
+}
+
+pub trait DeltaLight {
+    /// None when the point gets no light from it at all
+    fn sample(&self, point: Point3) -> Option<LightSample>;
+}
+
+pub type DeltaLightSync = dyn DeltaLight + Send + Sync;
+
+/// How a point light's intensity varies with direction
+enum Distribution {
+    Isotropic,
+    Spot { cos_inner: f64, cos_outer: f64 },
+    Ies(Arc<IesProfile>)
+}
+
+/// Intensities are in W/sr in the same units as DiffuseLight, a color of luminance 1
+/// at intensity 1 lights a surface 1m away with an irradiance of 1 W/m^2.
+pub struct PointLight {
+    position: Point3,
+    axis: Vec3,     // the spot direction, or an IES fixture's nadir
+    tangent: Vec3,  // an IES fixture's horizontal angle 0
+    bitangent: Vec3,
+    intensity: Color,
+    distribution: Distribution
+}
+
+impl PointLight {
+    /// Emit a total power in watts evenly in all directions
+    pub fn new(position: Point3, color: Color, watts: f64) -> Self {
+        PointLight::new_with(position, Vec3::new(0.0, -1.0, 0.0), color, watts / (4.0 * PI), Distribution::Isotropic)
+    }
+
+    /// Cone of light around an axis, at full intensity inside the inner angle and
+    /// fading out smoothly towards the outer one. Angles are in degrees from the axis.
+    pub fn new_spot(position: Point3,
+                    axis: Vec3,
+                    color: Color,
+                    intensity: f64,
+                    inner_angle: f64,
+                    outer_angle: f64) -> Self {
+        let cos_inner = inner_angle.to_radians().cos();
+        let cos_outer = outer_angle.max(inner_angle).to_radians().cos();
+
+        PointLight::new_with(position, axis, color, intensity, Distribution::Spot { cos_inner, cos_outer })
+    }
+
+    /// Light fixture with a measured distribution, pointing its nadir along the axis.
+    /// The profile's candela are converted to W/sr, then multiplied by the scale.
+    pub fn new_ies(position: Point3, axis: Vec3, color: Color, profile: Arc<IesProfile>, scale: f64) -> Self {
+        PointLight::new_with(position, axis, color, scale / LUMINOUS_EFFICACY, Distribution::Ies(profile))
     }
-}
\ No newline at end of file
+
+    /// The color only sets the hue, its brightness is normalized away
+    fn new_with(position: Point3, axis: Vec3, color: Color, intensity: f64, distribution: Distribution) -> Self {
+        let axis = axis.unit();
+        let (tangent, bitangent) = orthonormal_basis(axis);
+
+        PointLight {
+            position,
+            axis,
+            tangent,
+            bitangent,
+            intensity: color / luminance(color) * intensity,
+            distribution
+        }
+    }
+
+    fn directional_factor(&self, direction: Vec3) -> f64 {
+        match &self.distribution {
+            Distribution::Isotropic => 1.0,
+            Distribution::Spot { cos_inner, cos_outer } => {
+                let cos_theta = direction.dot(self.axis);
+                if cos_theta >= *cos_inner {
+                    return 1.0;
+                }
+                let t = ((cos_theta - cos_outer) / (cos_inner - cos_outer)).clamp(0.0, 1.0);
+                t * t * (3.0 - 2.0 * t)
+            }
+            Distribution::Ies(profile) => {
+                // Fixture frame has its nadir at -Z
+                let local = Vec3::new(direction.dot(self.tangent), direction.dot(self.bitangent), -direction.dot(self.axis));
+                profile.candela(local)
+            }
+        }
+    }
+}
+
+impl DeltaLight for PointLight {
+    fn sample(&self, point: Point3) -> Option<LightSample> {
+        let to_light = self.position - point;
+        let distance = to_light.length();
+        let direction = to_light / distance;
+
+        let factor = self.directional_factor(-direction);
+        if factor <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: self.intensity * factor / (distance * distance)
+        })
+    }
+}
+
+/// Gobo or slide projector, projects a texture into the scene through a perspective frustum.
+/// The texture's (u, v) span the frustum, v going up.
+pub struct ProjectorLight {
+    position: Point3,
+    forward: Vec3,
+    right: Vec3,
+    up: Vec3,
+    tan_half_width: f64,
+    tan_half_height: f64,
+    texture: Arc<TextureSync>,
+    intensity: f64
+}
+
+impl ProjectorLight {
+    /// vfov is in degrees, aspect is width over height, intensity is in W/sr along the center of the frustum.
+    pub fn new(position: Point3,
+               look_at: Point3,
+               vup: Vec3,
+               vfov: f64,
+               aspect: f64,
+               texture: Arc<TextureSync>,
+               intensity: f64) -> Self {
+        let forward = (look_at - position).unit();
+        let right = forward.cross(vup).unit();
+        let up = right.cross(forward);
+        let tan_half_height = (vfov.to_radians() / 2.0).tan();
+
+        ProjectorLight {
+            position,
+            forward,
+            right,
+            up,
+            tan_half_width: tan_half_height * aspect,
+            tan_half_height,
+            texture,
+            intensity
+        }
+    }
+}
+
+impl DeltaLight for ProjectorLight {
+    fn sample(&self, point: Point3) -> Option<LightSample> {
+        let from_light = point - self.position;
+        let depth = from_light.dot(self.forward);
+        if depth <= 0.0 {
+            return None;
+        }
+
+        // Position on the image plane at distance 1
+        let x = from_light.dot(self.right) / depth;
+        let y = from_light.dot(self.up) / depth;
+        if x.abs() > self.tan_half_width || y.abs() > self.tan_half_height {
+            return None;
+        }
+        let u = 0.5 + x / (2.0 * self.tan_half_width);
+        let v = 0.5 + y / (2.0 * self.tan_half_height);
+
+        let distance = from_light.length();
+        // Same power through every texel, texels away from the center cover less solid angle.
+        // That lights a wall facing the projector evenly.
+        let cos_theta = depth / distance;
+        let falloff = 1.0 / (cos_theta * cos_theta * cos_theta);
+
+        Some(LightSample {
+            direction: -from_light / distance,
+            distance,
+            radiance: self.texture.value(u, v, &point, &-self.forward) * self.intensity * falloff / (distance * distance)
+        })
+    }
+}
@@ -0,0 +1,257 @@
+//! Many-light sampling with a light hierarchy.
+//!
+//! Emissive surfaces are collected from the world once it is built, and arranged in a
+//! tree where every node bounds its emitters' positions, emission directions and power.
+//! At every shading point the integrator walks down the tree, picking a child in
+//! proportion to an estimate of how much light it sends there, and samples a single
+//! emitter with a shadow ray.
+//!
+//! "Importance Sampling of Many Lights with Adaptive Tree Splitting" (Conty Estevez & Kulla 2018)
+
+use std::collections::HashSet;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use rand::random;
+
+use crate::geometry::{Ray, hittable::{HitRecord, ObjectId}};
+use crate::math::{spectrum::luminance, vec3::{Point3, Vec3}};
+
+use super::{aabb::AABB, material::MaterialSync};
+
+
+pub trait Emitter {
+    /// Pick a point on the emitter to send a shadow ray to from the origin.
+    /// Returns the point and the pdf of the direction towards it, per solid angle.
+    fn sample_point(&self, origin: Point3) -> Option<(Point3, f64)>;
+    fn light_bounds(&self) -> LightBounds;
+    /// The object whose hits are on this emitter
+    fn object_id(&self) -> ObjectId;
+}
+
+pub type EmitterSync = dyn Emitter + Send + Sync;
+
+/// Where light comes from, in which directions it goes, and how much of it
+#[derive(Clone, Copy)]
+pub struct LightBounds {
+    pub bbox: AABB,
+    pub axis: Vec3,         // center of the cone of surface normals
+    pub theta_normals: f64, // spread of the normals around the axis
+    pub theta_emission: f64, // spread of the emitted light around each normal
+    pub power: f64          // estimated, in units of luminance
+}
+
+impl LightBounds {
+    /// Bounds of a flat emitter given how much it emits from each side of its normal
+    pub fn new_surface(bbox: AABB, normal: Vec3, front: f64, back: f64, area: f64) -> Self {
+        let (axis, theta_normals) = match (front > 0.0, back > 0.0) {
+            (true, true) => (normal, PI),
+            (false, true) => (-normal, 0.0),
+            _ => (normal, 0.0)
+        };
+
+        LightBounds {
+            bbox,
+            axis,
+            theta_normals,
+            theta_emission: PI / 2.0,
+            power: (front + back) * PI * area
+        }
+    }
+
+    pub fn union(&self, other: &LightBounds) -> LightBounds {
+        let (axis, theta_normals) = cone_union(
+            (self.axis, self.theta_normals),
+            (other.axis, other.theta_normals)
+        );
+
+        LightBounds {
+            bbox: self.bbox + other.bbox,
+            axis,
+            theta_normals,
+            theta_emission: self.theta_emission.max(other.theta_emission),
+            power: self.power + other.power
+        }
+    }
+
+    fn centroid(&self) -> Point3 {
+        Point3::new(
+            (self.bbox.x.min + self.bbox.x.max) / 2.0,
+            (self.bbox.y.min + self.bbox.y.max) / 2.0,
+            (self.bbox.z.min + self.bbox.z.max) / 2.0
+        )
+    }
+
+    /// Conservative estimate of the light arriving at a point
+    pub fn importance(&self, point: Point3) -> f64 {
+        if self.power <= 0.0 {
+            return 0.0;
+        }
+
+        let center = self.centroid();
+        let radius = Vec3::new(self.bbox.x.len(), self.bbox.y.len(), self.bbox.z.len()).length() / 2.0;
+        let to_point = point - center;
+        let distance_squared = to_point.length_squared();
+
+        // Inside the bounds light can come from any direction
+        if distance_squared <= radius * radius {
+            return self.power / (radius * radius).max(f64::EPSILON);
+        }
+
+        let distance = distance_squared.sqrt();
+        let theta = (to_point / distance).dot(self.axis).clamp(-1.0, 1.0).acos();
+        let theta_bounds = (radius / distance).asin();
+
+        // Smallest angle between any emitting normal and the point
+        let theta_min = (theta - self.theta_normals - theta_bounds).max(0.0);
+        if theta_min >= self.theta_emission {
+            return 0.0;
+        }
+
+        self.power * theta_min.cos() / distance_squared
+    }
+}
+
+/// Smallest cone containing two cones, each given as (axis, spread angle)
+fn cone_union(a: (Vec3, f64), b: (Vec3, f64)) -> (Vec3, f64) {
+    let ((axis_a, theta_a), (axis_b, theta_b)) = if a.1 >= b.1 { (a, b) } else { (b, a) };
+    let theta_between = axis_a.dot(axis_b).clamp(-1.0, 1.0).acos();
+
+    if (theta_between + theta_b).min(PI) <= theta_a {
+        return (axis_a, theta_a);
+    }
+
+    let theta = (theta_a + theta_between + theta_b) / 2.0;
+    if theta >= PI {
+        return (axis_a, PI);
+    }
+
+    // Rotate a's axis towards b's so both fit
+    let rotation_axis = axis_a.cross(axis_b);
+    if rotation_axis.near_zero() {
+        return (axis_a, PI);
+    }
+
+    (axis_a.rotate_rodrigues(theta - theta_a, rotation_axis), theta)
+}
+
+/// Average luminance a material emits from the front and the back of a surface,
+/// estimated at a few points on it. Points are given as (point, outward normal, u, v).
+pub fn emitted_luminance(material: &Arc<MaterialSync>, points: &[(Point3, Vec3, f64, f64)]) -> (f64, f64) {
+    let mut front = 0.0;
+    let mut back = 0.0;
+
+    for &(point, normal, u, v) in points {
+        let from_front = Ray::new(point + normal, -normal, 0.0);
+        let from_back = Ray::new(point - normal, normal, 0.0);
+
+        let front_hit = HitRecord::new(point, normal, material.clone(), 1.0, u, v, from_front);
+        let back_hit = HitRecord::new(point, normal, material.clone(), 1.0, u, v, from_back);
+
+        front += luminance(material.emitted(&from_front, &front_hit));
+        back += luminance(material.emitted(&from_back, &back_hit));
+    }
+
+    let count = points.len().max(1) as f64;
+    (front / count, back / count)
+}
+
+
+// Light BVH
+// =========
+enum LightNode {
+    Leaf { emitter: Arc<EmitterSync>, bounds: LightBounds },
+    Interior { left: Box<LightNode>, right: Box<LightNode>, bounds: LightBounds }
+}
+
+impl LightNode {
+    fn bounds(&self) -> &LightBounds {
+        match self {
+            LightNode::Leaf { bounds, .. } | LightNode::Interior { bounds, .. } => bounds
+        }
+    }
+}
+
+pub struct LightBVH {
+    root: LightNode,
+    object_ids: HashSet<ObjectId>  // objects emitting light through the tree
+}
+
+impl LightBVH {
+    /// None if there is nothing emitting light
+    pub fn new(emitters: Vec<Arc<EmitterSync>>) -> Option<Self> {
+        let mut leaves: Vec<(Arc<EmitterSync>, LightBounds)> = emitters
+            .into_iter()
+            .map(|emitter| {
+                let bounds = emitter.light_bounds();
+                (emitter, bounds)
+            })
+            .filter(|(_, bounds)| bounds.power > 0.0)
+            .collect();
+
+        if leaves.is_empty() {
+            return None;
+        }
+
+        let object_ids = leaves.iter().map(|(emitter, _)| emitter.object_id()).collect();
+        Some(LightBVH { root: LightBVH::build(&mut leaves), object_ids })
+    }
+
+    /// Whether the tree samples the emission of an object, so it can be
+    /// skipped when a bounce hits the object
+    pub fn contains(&self, object_id: ObjectId) -> bool {
+        self.object_ids.contains(&object_id)
+    }
+
+    /// Split at the median along the longest axis of the emitters' centroids
+    fn build(leaves: &mut [(Arc<EmitterSync>, LightBounds)]) -> LightNode {
+        if leaves.len() == 1 {
+            let (emitter, bounds) = leaves[0].clone();
+            return LightNode::Leaf { emitter, bounds };
+        }
+
+        let mut centroid_bounds = AABB::default();
+        for (_, bounds) in leaves.iter() {
+            let centroid = bounds.centroid();
+            centroid_bounds += AABB::new_from_points(centroid, centroid);
+        }
+        let axis = centroid_bounds.longest_axis();
+        leaves.sort_by(|(_, a), (_, b)| a.centroid()[axis].total_cmp(&b.centroid()[axis]));
+
+        let (left_leaves, right_leaves) = leaves.split_at_mut(leaves.len() / 2);
+        let left = LightBVH::build(left_leaves);
+        let right = LightBVH::build(right_leaves);
+        let bounds = left.bounds().union(right.bounds());
+
+        LightNode::Interior { left: Box::new(left), right: Box::new(right), bounds }
+    }
+
+    /// Pick an emitter for a shading point, returns it with the probability it was picked at
+    pub fn sample(&self, point: Point3) -> Option<(&Arc<EmitterSync>, f64)> {
+        let mut node = &self.root;
+        let mut probability = 1.0;
+
+        loop {
+            match node {
+                LightNode::Leaf { emitter, .. } => return Some((emitter, probability)),
+                LightNode::Interior { left, right, .. } => {
+                    let left_importance = left.bounds().importance(point);
+                    let right_importance = right.bounds().importance(point);
+                    let total = left_importance + right_importance;
+                    if total <= 0.0 {
+                        return None;
+                    }
+
+                    let left_probability = left_importance / total;
+                    if random::<f64>() < left_probability {
+                        node = left;
+                        probability *= left_probability;
+                    } else {
+                        node = right;
+                        probability *= 1.0 - left_probability;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,11 +1,14 @@
+use std::f64::consts::PI;
 use std::sync::Arc;
 
 use crate::geometry::{Ray, hittable::HitRecord};
 use crate::math::utils::random_unit_vector;
 use crate::rendering::color::{COLOR_WHITE, COLOR_BLACK};
 use crate::graphics::texture::{TextureSync, SolidColorTexture};
+use crate::graphics::medium::Medium;
+use crate::graphics::compositing::Compositing;
 use crate::math::{
-    optics::{reflect, refract},
+    optics::{reflect, refract, reflectance},
     vec3::{Vec3, Color, Point3},
     utils::{get_random_point_in_unit_sphere, get_random_point_on_unit_sphere}
 };
@@ -15,10 +18,47 @@ use rand::random;
 pub trait Material {
     fn scatter(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<(Color, Ray)>;
 
+    /// Light emitted towards the incident ray's origin
     #[allow(unused_variables)]
-    fn emitted(&self, u: f64, v: f64, point: &Point3) -> Color {
+    fn emitted(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Color {
         COLOR_BLACK
     }
+
+    /// How opaque the surface is at a given point, in [0, 1].
+    /// Geometry treats hits on transparent parts as misses.
+    #[allow(unused_variables)]
+    fn opacity(&self, u: f64, v: f64, point: &Point3, normal: &Vec3) -> f64 {
+        1.0
+    }
+
+    /// How hits seen directly by the camera end up in the alpha channel.
+    #[allow(unused_variables)]
+    fn compositing(&self, hitrec: &HitRecord) -> Compositing {
+        Compositing::Opaque
+    }
+
+    /// Fraction of the light arriving from a direction that is scattered back along the
+    /// incident ray, the BSDF times the cosine. Lets the integrator sample lights directly.
+    /// None for materials that can only be sampled through scatter(), like mirrors and glass.
+    #[allow(unused_variables)]
+    fn eval(&self, incident_ray: &Ray, hitrec: &HitRecord, direction: Vec3) -> Option<Color> {
+        None
+    }
+
+    /// Flat shading used by the toon integrator instead of tracing the path further.
+    /// Materials without one are path traced as usual.
+    #[allow(unused_variables)]
+    fn toon_shade(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<Color> {
+        None
+    }
+
+    /// The material that stands in for this one at a hit, for materials that randomly
+    /// pick between others. The camera picks once per hit before compositing and shading,
+    /// so every decision made at the hit agrees on the same material.
+    #[allow(unused_variables)]
+    fn pick(&self, hitrec: &HitRecord) -> Option<Arc<MaterialSync>> {
+        None
+    }
 }
 
 // ? wtf is this, read about it
@@ -49,10 +89,16 @@ impl Material for Lambertian {
         if scatter_direction.near_zero() { scatter_direction = hitrec.normal; }
 
         Some((
-            self.albedo.value(hitrec.u, hitrec.v, &hitrec.point), 
-            Ray::new(hitrec.point, scatter_direction, incident_ray.time)
+            self.albedo.value_footprint(hitrec.u, hitrec.v, &hitrec.point, &hitrec.geometric_normal, &hitrec.footprint), 
+            incident_ray.spawn(hitrec.point, scatter_direction)
         ))
     }
+
+    fn eval(&self, _incident_ray: &Ray, hitrec: &HitRecord, direction: Vec3) -> Option<Color> {
+        let cos_theta = hitrec.normal.dot(direction).max(0.0);
+
+        Some(self.albedo.value_footprint(hitrec.u, hitrec.v, &hitrec.point, &hitrec.geometric_normal, &hitrec.footprint) * cos_theta / PI)
+    }
 }
 
 // TODO: implement a Vantablack material
@@ -107,11 +153,12 @@ impl Metal {
 impl Material for Metal {
     fn scatter(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<(Color, Ray)> {
         let reflected_direction = reflect(incident_ray.direction.unit(), hitrec.normal);
-        let scattered_ray = Ray::new(
-            hitrec.point, 
-            reflected_direction + self.fuzz * get_random_point_in_unit_sphere(), 
-            incident_ray.time
-        );
+        // Differentials only follow perfect mirrors, fuzz spreads the footprint out
+        let scattered_ray = if self.fuzz == 0.0 {
+            incident_ray.spawn_reflected(hitrec, reflected_direction)
+        } else {
+            incident_ray.spawn(hitrec.point, reflected_direction + self.fuzz * get_random_point_in_unit_sphere())
+        };
 
         if scattered_ray.direction.dot(hitrec.normal) > 0.0 {
             Some((self.albedo, scattered_ray))
@@ -123,44 +170,62 @@ impl Material for Metal {
 
 // Dialectics
 // ==========
-#[derive(Default)]
 pub struct Dielectric {
-    pub ir: f64 // index of refraction
+    medium: Medium
 }
 
-fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
-    let r0 = ((1.0 -  ref_idx) / (1.0 + ref_idx)).powi(2);
-    
-    r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+impl Dielectric {
+    pub fn new(ir: f64) -> Self {
+        Dielectric::new_with_priority(ir, 1)
+    }
+
+    /// Where dielectrics overlap, the one with the highest priority decides the
+    /// index of refraction. E.g. a glass of water can be a solid glass object with the
+    /// water, slightly smaller, inside it at a higher priority: the water takes over
+    /// everywhere but the glass walls.
+    pub fn new_with_priority(ir: f64, priority: u32) -> Self {
+        Dielectric { medium: Medium::new(ir, priority) }
+    }
 }
 
 impl Material for Dielectric {
     fn scatter(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<(Color, Ray)> {
-        // If the ray comes from the outside, assume the refraction index outside the 
-        // object is 1.0
-        let refraction_ratio = if hitrec.front_face { 1.0 / self.ir } else { self.ir };
+        // Find the media on both sides of the interface from the media the ray is in
+        let crossing = incident_ray.media.cross(self.medium, hitrec.front_face);
+
+        // A higher priority medium owns this region, carry on as if nothing was hit
+        if crossing.is_false_interface {
+            let mut passed_ray = incident_ray.spawn_refracted(hitrec, incident_ray.direction, 1.0);
+            passed_ray.media = crossing.transmitted_media;
+
+            return Some((COLOR_WHITE, passed_ray));
+        }
+
+        let refraction_ratio = crossing.eta_incident / crossing.eta_transmitted;
 
         let unit_direction = incident_ray.direction.unit();
         let cos_theta = -unit_direction.dot(hitrec.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
 
-        let direction;
         // if ray cannot refract, it gets reflected
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
         let some_bullshit = reflectance(cos_theta, refraction_ratio) > random::<f64>();
 
-        if cannot_refract || some_bullshit {
-            direction = reflect(unit_direction, hitrec.normal);
+        let scattered_ray = if cannot_refract || some_bullshit {
+            incident_ray.spawn_reflected(hitrec, reflect(unit_direction, hitrec.normal))
         } else {
             // TODO: Return 2 rays maybe? one reflects, one refracts
             // play with random weights for each ray's attenutation
-            direction = refract(unit_direction, hitrec.normal, refraction_ratio);
-        }
-
-        Some((
-            COLOR_WHITE,
-            Ray::new(hitrec.point, direction, incident_ray.time)
-        ))
+            let mut refracted_ray = incident_ray.spawn_refracted(
+                hitrec,
+                refract(unit_direction, hitrec.normal, refraction_ratio),
+                refraction_ratio
+            );
+            refracted_ray.media = crossing.transmitted_media;
+            refracted_ray
+        };
+
+        Some((COLOR_WHITE, scattered_ray))
     }
 }
 
@@ -184,9 +249,108 @@ impl Isotropic {
 
 impl Material for Isotropic {
     fn scatter(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<(Color, Ray)> {
-        let scattered = Ray::new(hitrec.point, random_unit_vector(), incident_ray.time);
-        let attentuation = self.texture.value(hitrec.u, hitrec.v, &hitrec.point);
+        // Scattering inside a volume doesn't cross any interface, the ray stays in the same media
+        let scattered = incident_ray.spawn(hitrec.point, random_unit_vector());
+        let attentuation = self.texture.value(hitrec.u, hitrec.v, &hitrec.point, &hitrec.geometric_normal);
 
         Some((attentuation, scattered))
     }
+
+    /// Scatters evenly in all directions, no cosine inside a volume
+    fn eval(&self, _incident_ray: &Ray, hitrec: &HitRecord, _direction: Vec3) -> Option<Color> {
+        Some(self.texture.value(hitrec.u, hitrec.v, &hitrec.point, &hitrec.geometric_normal) / (4.0 * PI))
+    }
+}
+
+
+// Mix material
+// ============
+
+pub enum MixMode {
+    /// Each point is fully one material or the other, whichever the mask is closer to
+    Select,
+    /// The mask is the probability of scattering off the second material
+    Blend
+}
+
+/// Mixes two materials across a surface by a scalar mask texture.
+/// Where the mask is 0 the first material is used, where it's 1 the second one.
+/// Either material can be a MixMaterial itself.
+pub struct MixMaterial {
+    first: Arc<MaterialSync>,
+    second: Arc<MaterialSync>,
+    mask: Arc<TextureSync>,
+    mode: MixMode
+}
+
+impl MixMaterial {
+    pub fn new_select(first: Arc<MaterialSync>, second: Arc<MaterialSync>, mask: Arc<TextureSync>) -> Self {
+        MixMaterial { first, second, mask, mode: MixMode::Select }
+    }
+
+    pub fn new_blend(first: Arc<MaterialSync>, second: Arc<MaterialSync>, mask: Arc<TextureSync>) -> Self {
+        MixMaterial { first, second, mask, mode: MixMode::Blend }
+    }
+
+    /// Weight of the second material at a point, in [0, 1]
+    fn weight(&self, u: f64, v: f64, point: &Point3, normal: &Vec3) -> f64 {
+        let mask = self.mask.scalar_value(u, v, point, normal).clamp(0.0, 1.0);
+
+        match self.mode {
+            MixMode::Select => if mask >= 0.5 { 1.0 } else { 0.0 },
+            MixMode::Blend => mask
+        }
+    }
+
+    /// One of the materials, picked with probability of its weight at the hit
+    fn pick_one(&self, hitrec: &HitRecord) -> &Arc<MaterialSync> {
+        let weight = self.weight(hitrec.u, hitrec.v, &hitrec.point, &hitrec.geometric_normal);
+
+        if random::<f64>() < weight { &self.second } else { &self.first }
+    }
+}
+
+impl Material for MixMaterial {
+    /// Scatter off one of the materials, picked with probability of its weight.
+    /// Over many samples this averages to the blend of both.
+    fn scatter(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<(Color, Ray)> {
+        self.pick_one(hitrec).scatter(incident_ray, hitrec)
+    }
+
+    /// Emission doesn't need to be sampled, just blend both.
+    fn emitted(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Color {
+        let weight = self.weight(hitrec.u, hitrec.v, &hitrec.point, &hitrec.geometric_normal);
+
+        (1.0 - weight) * self.first.emitted(incident_ray, hitrec) + weight * self.second.emitted(incident_ray, hitrec)
+    }
+
+    /// Can only be evaluated if every material that contributes here can be
+    fn eval(&self, incident_ray: &Ray, hitrec: &HitRecord, direction: Vec3) -> Option<Color> {
+        let weight = self.weight(hitrec.u, hitrec.v, &hitrec.point, &hitrec.geometric_normal);
+        let first = if weight < 1.0 { self.first.eval(incident_ray, hitrec, direction)? } else { COLOR_BLACK };
+        let second = if weight > 0.0 { self.second.eval(incident_ray, hitrec, direction)? } else { COLOR_BLACK };
+
+        Some((1.0 - weight) * first + weight * second)
+    }
+
+    fn opacity(&self, u: f64, v: f64, point: &Point3, normal: &Vec3) -> f64 {
+        let weight = self.weight(u, v, point, normal);
+
+        (1.0 - weight) * self.first.opacity(u, v, point, normal) + weight * self.second.opacity(u, v, point, normal)
+    }
+
+    /// The camera calls pick() first and never gets here,
+    /// unless the mix is the base of a layered material
+    fn compositing(&self, hitrec: &HitRecord) -> Compositing {
+        self.pick_one(hitrec).compositing(hitrec)
+    }
+
+    fn toon_shade(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<Color> {
+        self.pick_one(hitrec).toon_shade(incident_ray, hitrec)
+    }
+
+    /// Picked like scatter(), so blended compositing averages out over the pixel's samples
+    fn pick(&self, hitrec: &HitRecord) -> Option<Arc<MaterialSync>> {
+        Some(self.pick_one(hitrec).clone())
+    }
 }
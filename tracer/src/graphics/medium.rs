@@ -0,0 +1,123 @@
+//! Interface priority stack for nested dielectrics.
+//!
+//! Every path carries the stack of media it is currently inside of, so the refraction
+//! ratio at a boundary uses the actual indices of refraction on both sides instead of
+//! assuming the outside is always air.
+//!
+//! Overlapping volumes (liquid in a glass, ice in water) are resolved with priorities,
+//! as described by Schmidt & Budge in "Simple Nested Dielectrics in Ray Traced Images":
+//! inside an overlap the medium with the highest priority wins, and the boundaries of
+//! lower priority media are false interfaces that rays pass straight through.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Index of refraction of the medium surrounding everything (air/vacuum).
+pub const OUTSIDE_IOR: f64 = 1.0;
+
+/// How many media a path can be nested in. Entering more than that is ignored.
+pub const MAX_NESTED_MEDIA: usize = 6;
+
+static NEXT_MEDIUM_ID: AtomicU32 = AtomicU32::new(1);
+
+
+#[derive(Clone, Copy, Default)]
+pub struct Medium {
+    pub id: u32,        // unique per medium instance, 0 is never assigned
+    pub priority: u32,  // higher priority wins inside overlapping volumes
+    pub ior: f64        // index of refraction
+}
+
+impl Medium {
+    pub fn new(ior: f64, priority: u32) -> Medium {
+        Medium {
+            id: NEXT_MEDIUM_ID.fetch_add(1, Ordering::Relaxed),
+            priority,
+            ior
+        }
+    }
+}
+
+/// What happens to a ray crossing the boundary of a medium.
+pub struct Crossing {
+    pub eta_incident: f64,      // IOR on the side the ray comes from
+    pub eta_transmitted: f64,   // IOR on the side the ray goes to
+    pub is_false_interface: bool,
+    pub transmitted_media: MediumStack  // the stack once the ray is across
+}
+
+/// Fixed size stack of the media enclosing a path.
+/// It is Copy so it can travel inside a Ray.
+#[derive(Clone, Copy, Default)]
+pub struct MediumStack {
+    media: [Medium; MAX_NESTED_MEDIA],
+    len: usize
+}
+
+impl MediumStack {
+    /// The medium the path is effectively in - the one with the highest priority.
+    /// On equal priorities the most recently entered one wins.
+    pub fn top(&self) -> Option<Medium> {
+        let mut top: Option<Medium> = None;
+
+        for medium in &self.media[..self.len] {
+            match top {
+                Some(current) if current.priority > medium.priority => {}
+                _ => { top = Some(*medium); }
+            }
+        }
+
+        top
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.media[..self.len].iter().any(|medium| medium.id == id)
+    }
+
+    pub fn push(&mut self, medium: Medium) {
+        if self.contains(medium.id) || self.len == MAX_NESTED_MEDIA {
+            return;
+        }
+
+        self.media[self.len] = medium;
+        self.len += 1;
+    }
+
+    pub fn remove(&mut self, id: u32) {
+        if let Some(index) = self.media[..self.len].iter().position(|medium| medium.id == id) {
+            self.media.copy_within(index + 1..self.len, index);
+            self.len -= 1;
+        }
+    }
+
+    /// Resolve a ray hitting the boundary of a medium.
+    ///
+    /// entering is true when the ray hits the boundary from the outside.
+    /// If a higher priority medium encloses the boundary, it is a false interface and
+    /// the ray should continue unchanged, only updating its stack.
+    pub fn cross(&self, medium: Medium, entering: bool) -> Crossing {
+        let mut transmitted_media = *self;
+
+        if entering {
+            transmitted_media.push(medium);
+            let outside = self.top();
+
+            Crossing {
+                eta_incident: outside.map_or(OUTSIDE_IOR, |m| m.ior),
+                eta_transmitted: medium.ior,
+                is_false_interface: outside.is_some_and(|m| m.priority > medium.priority),
+                transmitted_media
+            }
+        } else {
+            // Whatever is left once we leave this medium is on the other side
+            transmitted_media.remove(medium.id);
+            let inside = transmitted_media.top();
+
+            Crossing {
+                eta_incident: medium.ior,
+                eta_transmitted: inside.map_or(OUTSIDE_IOR, |m| m.ior),
+                is_false_interface: inside.is_some_and(|m| m.priority > medium.priority),
+                transmitted_media
+            }
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//! Measured BRDFs from the MERL database.
+//!
+//! "A Data-Driven Reflectance Model" (Matusik et al. 2003).
+//! A .binary file holds three i32 dimensions followed by the red, green and blue tables
+//! of f64 reflectance, indexed by the half angle and difference angle coordinates
+//! of "A New Change of Variables for Efficient BRDF Representation" (Rusinkiewicz 1998).
+
+use std::f64::consts::PI;
+use std::fs;
+use std::io::{Error, ErrorKind};
+
+use rand::random;
+
+use crate::geometry::{Ray, hittable::HitRecord};
+use crate::math::{spectrum::luminance, utils::get_random_point_on_unit_sphere, vec3::{Color, Vec3}};
+
+use super::{material::Material, microfacet::{to_local, to_world}};
+
+const THETA_H_RES: usize = 90;
+const THETA_D_RES: usize = 90;
+const PHI_D_RES: usize = 180;   // half of the full 360, the data is reciprocal
+const TABLE_SIZE: usize = THETA_H_RES * THETA_D_RES * PHI_D_RES;
+
+const RED_SCALE: f64 = 1.0 / 1500.0;
+const GREEN_SCALE: f64 = 1.15 / 1500.0;
+const BLUE_SCALE: f64 = 1.66 / 1500.0;
+
+// Resolution of the tabulated importance sampling distribution
+const VIEW_BINS: usize = 32;
+const THETA_BINS: usize = 32;
+const PHI_BINS: usize = 64;
+
+
+pub enum MerlSampling {
+    /// Cosine weighted hemisphere, fine for diffuse-ish materials
+    Cosine,
+    /// Sample directions by a table of the BRDF's luminance per view angle,
+    /// much less noise on glossy materials
+    Tabulated
+}
+
+pub struct MerlMaterial {
+    table: Vec<f32>,        // red, green and blue tables one after the other, already scaled
+    sampling: MerlSampling,
+    sampling_cdfs: Vec<Vec<f64>>    // per view angle bin, over (theta, phi) cells
+}
+
+impl MerlMaterial {
+    pub fn load(path: &str, sampling: MerlSampling) -> std::io::Result<Self> {
+        let bytes = fs::read(path).map_err(|err| Error::new(err.kind(), format!("{path}: {err}")))?;
+        let header_size = 3 * std::mem::size_of::<i32>();
+        if bytes.len() < header_size {
+            return Err(Error::new(ErrorKind::InvalidData, format!("{path}: missing MERL header")));
+        }
+
+        let dimensions: Vec<i32> = bytes[..header_size]
+            .chunks_exact(4)
+            .map(|chunk| i32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        if dimensions.iter().product::<i32>() as usize != TABLE_SIZE {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("{path}: unexpected MERL dimensions {dimensions:?}")
+            ));
+        }
+
+        let data = &bytes[header_size..];
+        if data.len() != 3 * TABLE_SIZE * std::mem::size_of::<f64>() {
+            return Err(Error::new(ErrorKind::InvalidData, format!("{path}: truncated MERL data")));
+        }
+
+        let table = data
+            .chunks_exact(8)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let scale = [RED_SCALE, GREEN_SCALE, BLUE_SCALE][i / TABLE_SIZE];
+                // Negative entries mark unmeasured directions below the horizon
+                (f64::from_le_bytes(chunk.try_into().unwrap()) * scale).max(0.0) as f32
+            })
+            .collect();
+
+        let mut material = MerlMaterial { table, sampling, sampling_cdfs: Vec::new() };
+        if let MerlSampling::Tabulated = material.sampling {
+            material.sampling_cdfs = material.build_sampling_cdfs();
+        }
+
+        Ok(material)
+    }
+
+    /// Look up the BRDF for two local directions, normal is +Z
+    pub fn brdf(&self, wi: Vec3, wo: Vec3) -> Color {
+        if wi.z() <= 0.0 || wo.z() <= 0.0 {
+            return Color::zero();
+        }
+
+        // Half angle
+        let half = (wi + wo).unit();
+        let theta_half = half.z().clamp(-1.0, 1.0).acos();
+        let phi_half = half.y().atan2(half.x());
+
+        // Difference angle, wi in the frame where the half vector is the normal
+        let diff = wi.rotate_z(-phi_half).rotate_y(-theta_half);
+        let theta_diff = diff.z().clamp(-1.0, 1.0).acos();
+        let phi_diff = diff.y().atan2(diff.x());
+
+        let index = phi_diff_index(phi_diff)
+                  + theta_diff_index(theta_diff) * PHI_D_RES
+                  + theta_half_index(theta_half) * PHI_D_RES * THETA_D_RES;
+
+        Color::new(
+            self.table[index] as f64,
+            self.table[index + TABLE_SIZE] as f64,
+            self.table[index + 2 * TABLE_SIZE] as f64
+        )
+    }
+
+    /// For every view angle bin, tabulate the BRDF luminance times cosine over
+    /// cells of the hemisphere, relative to the view's azimuth.
+    fn build_sampling_cdfs(&self) -> Vec<Vec<f64>> {
+        let (d_theta, d_phi) = (PI / 2.0 / THETA_BINS as f64, 2.0 * PI / PHI_BINS as f64);
+
+        (0..VIEW_BINS).map(|view_bin| {
+            let cos_o = (view_bin as f64 + 0.5) / VIEW_BINS as f64;
+            let wo = Vec3::new((1.0 - cos_o * cos_o).sqrt(), 0.0, cos_o);
+
+            let weights: Vec<f64> = (0..THETA_BINS * PHI_BINS).map(|cell| {
+                let theta = (cell / PHI_BINS) as f64 * d_theta + d_theta / 2.0;
+                let phi = (cell % PHI_BINS) as f64 * d_phi + d_phi / 2.0;
+                let wi = spherical_direction(theta, phi);
+
+                luminance(self.brdf(wi, wo)) * theta.cos() * theta.sin() * d_theta * d_phi
+            }).collect();
+
+            // Keep a floor so no direction the BRDF reflects to is left out
+            let floor = 0.01 * weights.iter().sum::<f64>() / weights.len() as f64 + 1e-12;
+            let mut cdf = Vec::with_capacity(weights.len());
+            let mut total = 0.0;
+            for weight in weights {
+                total += weight + floor;
+                cdf.push(total);
+            }
+            cdf.iter_mut().for_each(|value| *value /= total);
+
+            cdf
+        }).collect()
+    }
+
+    /// Sample an incoming direction by the tabulated distribution.
+    /// Returns the local direction and its pdf in solid angle.
+    fn sample_tabulated(&self, wo: Vec3) -> (Vec3, f64) {
+        let view_bin = ((wo.z() * VIEW_BINS as f64) as usize).min(VIEW_BINS - 1);
+        let cdf = &self.sampling_cdfs[view_bin];
+        let (d_theta, d_phi) = (PI / 2.0 / THETA_BINS as f64, 2.0 * PI / PHI_BINS as f64);
+
+        let (cell, cell_probability) = pick_cell(cdf, random::<f64>());
+
+        let theta = ((cell / PHI_BINS) as f64 + random::<f64>()) * d_theta;
+        let phi = ((cell % PHI_BINS) as f64 + random::<f64>()) * d_phi + wo.y().atan2(wo.x());
+        let pdf = cell_probability / (d_theta * d_phi * theta.sin().max(1e-6));
+
+        (spherical_direction(theta, phi), pdf)
+    }
+}
+
+impl Material for MerlMaterial {
+    fn scatter(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<(Color, Ray)> {
+        let wo = to_local(-incident_ray.direction.unit(), hitrec.tangent, hitrec.bitangent, hitrec.normal);
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let (wi, pdf) = match self.sampling {
+            MerlSampling::Cosine => {
+                let wi = (Vec3::new(0.0, 0.0, 1.0) + get_random_point_on_unit_sphere()).unit();
+                (wi, wi.z() / PI)
+            }
+            MerlSampling::Tabulated => self.sample_tabulated(wo)
+        };
+        if wi.z() <= 0.0 || pdf <= 0.0 {
+            return None;
+        }
+
+        let attenuation = self.brdf(wi, wo) * wi.z() / pdf;
+        let direction = to_world(wi, hitrec.tangent, hitrec.bitangent, hitrec.normal);
+
+        Some((attenuation, incident_ray.spawn(hitrec.point, direction)))
+    }
+
+    fn eval(&self, incident_ray: &Ray, hitrec: &HitRecord, direction: Vec3) -> Option<Color> {
+        let wo = to_local(-incident_ray.direction.unit(), hitrec.tangent, hitrec.bitangent, hitrec.normal);
+        let wi = to_local(direction, hitrec.tangent, hitrec.bitangent, hitrec.normal);
+
+        Some(self.brdf(wi, wo) * wi.z().max(0.0))
+    }
+}
+
+/// The cell a uniform random number in [0, 1) falls in by the CDF, and the cell's probability
+fn pick_cell(cdf: &[f64], xi: f64) -> (usize, f64) {
+    let cell = cdf.partition_point(|&value| value <= xi).min(cdf.len() - 1);
+    let cell_probability = cdf[cell] - if cell > 0 { cdf[cell - 1] } else { 0.0 };
+
+    (cell, cell_probability)
+}
+
+fn spherical_direction(theta: f64, phi: f64) -> Vec3 {
+    Vec3::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos())
+}
+
+/// The half angle is sampled more densely close to the normal, where highlights are
+fn theta_half_index(theta_half: f64) -> usize {
+    if theta_half <= 0.0 {
+        return 0;
+    }
+
+    let degrees_scaled = theta_half / (PI / 2.0) * THETA_H_RES as f64;
+    ((degrees_scaled * THETA_H_RES as f64).sqrt() as usize).min(THETA_H_RES - 1)
+}
+
+fn theta_diff_index(theta_diff: f64) -> usize {
+    ((theta_diff / (PI / 2.0) * THETA_D_RES as f64) as usize).min(THETA_D_RES - 1)
+}
+
+/// Reciprocity makes phi_diff and phi_diff + PI the same
+fn phi_diff_index(phi_diff: f64) -> usize {
+    let phi_diff = if phi_diff < 0.0 { phi_diff + PI } else { phi_diff };
+
+    ((phi_diff / PI * PHI_D_RES as f64) as usize).min(PHI_D_RES - 1)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pick_cell_follows_the_cdf() {
+        let cdf = [0.25, 0.25, 0.75, 1.0];
+
+        assert_eq!(pick_cell(&cdf, 0.0), (0, 0.25));
+        assert_eq!(pick_cell(&cdf, 0.3), (2, 0.5));
+        assert_eq!(pick_cell(&cdf, 0.8), (3, 0.25));
+        // The empty cell is never picked, even on its boundary
+        assert_eq!(pick_cell(&cdf, 0.25).0, 2);
+    }
+
+    #[test]
+    fn picked_cells_match_their_probabilities() {
+        let cdf = [0.1, 0.4, 0.5, 1.0];
+        let samples = 100_000;
+        let mut counts = [0usize; 4];
+        for i in 0..samples {
+            counts[pick_cell(&cdf, (i as f64 + 0.5) / samples as f64).0] += 1;
+        }
+
+        for (cell, count) in counts.iter().enumerate() {
+            let (_, probability) = pick_cell(&cdf, if cell > 0 { cdf[cell - 1] } else { 0.0 });
+            assert!((*count as f64 / samples as f64 - probability).abs() < 1e-3);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Microfacet materials.
+//!
+//! The surface is modeled as tiny mirrors (microfacets) whose normals follow the GGX
+//! distribution. Roughness can differ along the tangent and the bitangent, which gives
+//! the stretched highlights of brushed metal and hair.
+//!
+//! Directions are sampled from the distribution of visible normals,
+//! "Sampling the GGX Distribution of Visible Normals" (Heitz 2018).
+
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use rand::random;
+
+use crate::geometry::{Ray, hittable::HitRecord};
+use crate::math::{optics::reflect, vec3::{Color, Vec3}};
+use crate::rendering::color::COLOR_WHITE;
+
+use super::{material::Material, texture::{TextureSync, SolidColorTexture}};
+
+
+/// Anisotropic GGX distribution, in the local shading frame where the normal is +Z
+/// and the tangent is +X.
+#[derive(Clone, Copy)]
+pub struct Ggx {
+    pub alpha_x: f64,   // roughness along the tangent
+    pub alpha_y: f64    // roughness along the bitangent
+}
+
+impl Ggx {
+    pub fn new(roughness_u: f64, roughness_v: f64) -> Self {
+        // Perfectly smooth surfaces make the math blow up
+        Ggx { alpha_x: roughness_u.max(0.001), alpha_y: roughness_v.max(0.001) }
+    }
+
+    /// Smith's auxiliary function for the masking of a local direction
+    pub fn lambda(&self, w: Vec3) -> f64 {
+        let tan2_scaled = (
+            (self.alpha_x * w.x()).powi(2) + (self.alpha_y * w.y()).powi(2)
+        ) / (w.z() * w.z());
+
+        (-1.0 + (1.0 + tan2_scaled).sqrt()) / 2.0
+    }
+
+    /// Fraction of the microfacets facing w that are visible from w
+    pub fn masking(&self, w: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Fraction of the microfacets visible from both directions
+    pub fn masking_shadowing(&self, wo: Vec3, wi: Vec3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Sample a microfacet normal visible from the local direction wo
+    pub fn sample_visible_normal(&self, wo: Vec3) -> Vec3 {
+        // Stretch the view so the distribution becomes a hemisphere
+        let vh = Vec3::new(self.alpha_x * wo.x(), self.alpha_y * wo.y(), wo.z()).unit();
+
+        // Orthonormal basis around the stretched view
+        let lensq = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if lensq > 0.0 {
+            Vec3::new(-vh.y(), vh.x(), 0.0) / lensq.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(t1);
+
+        // Uniform point on a disk, warped to the visible half
+        let r = random::<f64>().sqrt();
+        let phi = 2.0 * PI * random::<f64>();
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+
+        // Project onto the hemisphere and unstretch
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+        Vec3::new(self.alpha_x * nh.x(), self.alpha_y * nh.y(), nh.z().max(0.0)).unit()
+    }
+}
+
+/// Express a world space direction in a local frame
+pub fn to_local(w: Vec3, tangent: Vec3, bitangent: Vec3, normal: Vec3) -> Vec3 {
+    Vec3::new(w.dot(tangent), w.dot(bitangent), w.dot(normal))
+}
+
+/// Express a local direction in world space
+pub fn to_world(w: Vec3, tangent: Vec3, bitangent: Vec3, normal: Vec3) -> Vec3 {
+    w.x() * tangent + w.y() * bitangent + w.z() * normal
+}
+
+
+// Anisotropic metal
+// =================
+
+/// Brushed metal, rougher across the brushing direction than along it.
+///
+/// The brushing direction follows the surface's tangent, i.e. the direction u grows in:
+/// around the Y axis on spheres, along the u edge on quads.
+/// The rotation texture turns it around the normal, [0, 1] maps to [0, PI].
+pub struct AnisotropicMetal {
+    albedo: Arc<TextureSync>,   // reflectance at normal incidence
+    distribution: Ggx,
+    rotation: Arc<TextureSync>
+}
+
+impl AnisotropicMetal {
+    pub fn new(albedo: Color, roughness_u: f64, roughness_v: f64) -> Self {
+        AnisotropicMetal {
+            albedo: Arc::new(SolidColorTexture::new(albedo)),
+            distribution: Ggx::new(roughness_u, roughness_v),
+            rotation: Arc::new(SolidColorTexture::new(Color::zero()))
+        }
+    }
+
+    pub fn new_texture(albedo: Arc<TextureSync>, roughness_u: f64, roughness_v: f64, rotation: Arc<TextureSync>) -> Self {
+        AnisotropicMetal { albedo, distribution: Ggx::new(roughness_u, roughness_v), rotation }
+    }
+}
+
+impl Material for AnisotropicMetal {
+    fn scatter(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<(Color, Ray)> {
+        // Turn the tangent frame to the brushing direction
+        let angle = PI * self.rotation.scalar_value(hitrec.u, hitrec.v, &hitrec.point, &hitrec.geometric_normal);
+        let tangent = angle.cos() * hitrec.tangent + angle.sin() * hitrec.bitangent;
+        let bitangent = hitrec.normal.cross(tangent);
+
+        let wo = to_local(-incident_ray.direction.unit(), tangent, bitangent, hitrec.normal);
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let microfacet_normal = self.distribution.sample_visible_normal(wo);
+        let wi = reflect(-wo, microfacet_normal);
+        if wi.z() <= 0.0 {
+            return None;
+        }
+
+        // Sampling visible normals leaves only Fresnel and the shadowing term in the weight
+        let f0 = self.albedo.value(hitrec.u, hitrec.v, &hitrec.point, &hitrec.geometric_normal);
+        let fresnel = f0 + (COLOR_WHITE - f0) * (1.0 - wo.dot(microfacet_normal)).powi(5);
+        let weight = self.distribution.masking_shadowing(wo, wi) / self.distribution.masking(wo);
+
+        let direction = to_world(wi, tangent, bitangent, hitrec.normal);
+        Some((fresnel * weight, incident_ray.spawn(hitrec.point, direction)))
+    }
+}
@@ -0,0 +1,182 @@
+//! Image pyramids for filtered texture lookups.
+//!
+//! Level 0 is the full resolution image, every next level is half the size of the one
+//! before, down to a single texel. Lookups pick the level whose texels match the size of
+//! the filter footprint, and blend the two nearest levels (trilinear filtering), so
+//! textures far away or at grazing angles don't alias.
+
+use std::sync::Arc;
+
+use crate::math::vec3::Color;
+
+
+/// What happens to texture coordinates outside [0, 1]
+#[derive(Clone, Copy, PartialEq)]
+pub enum WrapMode {
+    Repeat,
+    Mirror,
+    Clamp
+}
+
+/// How texels are interpolated within a level
+#[derive(Clone, Copy, PartialEq)]
+pub enum FilterMode {
+    Nearest,
+    Bilinear,
+    /// Catmull-Rom spline over 4x4 texels, sharper than bilinear when magnified
+    Bicubic
+}
+
+struct MipLevel {
+    width: usize,
+    height: usize,
+    texels: Vec<[f32; 3]>   // rows top to bottom
+}
+
+impl MipLevel {
+    /// Half the size, each texel the average of the (up to) 2x2 texels it covers
+    fn downsample(&self) -> MipLevel {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut texels = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0.0; 3];
+                let (x0, x1) = (2 * x, (2 * x + 1).min(self.width - 1));
+                let (y0, y1) = (2 * y, (2 * y + 1).min(self.height - 1));
+
+                for (sx, sy) in [(x0, y0), (x1, y0), (x0, y1), (x1, y1)] {
+                    let texel = self.texels[sy * self.width + sx];
+                    for c in 0..3 {
+                        sum[c] += texel[c] / 4.0;
+                    }
+                }
+                texels.push(sum);
+            }
+        }
+
+        MipLevel { width, height, texels }
+    }
+}
+
+/// Cloning is cheap, clones share the same levels
+#[derive(Clone)]
+pub struct MipMap {
+    levels: Arc<Vec<MipLevel>>,
+    pub filter: FilterMode,
+    pub wrap_u: WrapMode,
+    pub wrap_v: WrapMode
+}
+
+impl MipMap {
+    /// Texels are given row by row, from the top of the image
+    pub fn new(width: usize, height: usize, texels: Vec<[f32; 3]>, filter: FilterMode, wrap_u: WrapMode, wrap_v: WrapMode) -> Self {
+        let mut levels = vec![MipLevel { width, height, texels }];
+        while levels.last().is_some_and(|level| level.width > 1 || level.height > 1) {
+            let next = levels.last().unwrap().downsample();
+            levels.push(next);
+        }
+
+        MipMap { levels: Arc::new(levels), filter, wrap_u, wrap_v }
+    }
+
+    /// The same image, sampled differently
+    pub fn with_sampling(&self, filter: FilterMode, wrap_u: WrapMode, wrap_v: WrapMode) -> Self {
+        MipMap { levels: self.levels.clone(), filter, wrap_u, wrap_v }
+    }
+
+    pub fn width(&self) -> usize {
+        self.levels[0].width
+    }
+
+    pub fn height(&self) -> usize {
+        self.levels[0].height
+    }
+
+    /// Look up the texture for a filter footprint of the given width, in texels of the
+    /// full resolution image. A width of 1 or less reads the full resolution image.
+    pub fn lookup(&self, u: f64, v: f64, width: f64) -> Color {
+        let level = width.max(1.0).log2().min((self.levels.len() - 1) as f64);
+        let lower = level.floor() as usize;
+        let t = level - lower as f64;
+
+        let color = self.lookup_level(lower, u, v);
+        if t <= 0.0 || lower + 1 >= self.levels.len() {
+            return color;
+        }
+
+        (1.0 - t) * color + t * self.lookup_level(lower + 1, u, v)
+    }
+
+    fn lookup_level(&self, index: usize, u: f64, v: f64) -> Color {
+        let level = &self.levels[index];
+        // Texel centers are at half integers, v goes up the image
+        let x = u * level.width as f64 - 0.5;
+        let y = (1.0 - v) * level.height as f64 - 0.5;
+
+        match self.filter {
+            FilterMode::Nearest => self.texel(level, x.round() as i64, y.round() as i64),
+            FilterMode::Bilinear => {
+                let (x0, y0) = (x.floor(), y.floor());
+                let (tx, ty) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+
+                let top = (1.0 - tx) * self.texel(level, x0, y0) + tx * self.texel(level, x0 + 1, y0);
+                let bottom = (1.0 - tx) * self.texel(level, x0, y0 + 1) + tx * self.texel(level, x0 + 1, y0 + 1);
+                (1.0 - ty) * top + ty * bottom
+            }
+            FilterMode::Bicubic => {
+                let (x0, y0) = (x.floor(), y.floor());
+                let weights_x = catmull_rom_weights(x - x0);
+                let weights_y = catmull_rom_weights(y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+
+                let mut color = Color::zero();
+                for (j, weight_y) in weights_y.iter().enumerate() {
+                    for (i, weight_x) in weights_x.iter().enumerate() {
+                        color += weight_x * weight_y * self.texel(level, x0 + i as i64 - 1, y0 + j as i64 - 1);
+                    }
+                }
+                // The spline overshoots around sharp edges
+                Color::new(color.x().max(0.0), color.y().max(0.0), color.z().max(0.0))
+            }
+        }
+    }
+
+    fn texel(&self, level: &MipLevel, x: i64, y: i64) -> Color {
+        let x = wrap(x, level.width, self.wrap_u);
+        let y = wrap(y, level.height, self.wrap_v);
+        let texel = level.texels[y * level.width + x];
+
+        Color::new(texel[0] as f64, texel[1] as f64, texel[2] as f64)
+    }
+}
+
+fn wrap(coordinate: i64, size: usize, mode: WrapMode) -> usize {
+    let size = size as i64;
+
+    let wrapped = match mode {
+        WrapMode::Repeat => coordinate.rem_euclid(size),
+        WrapMode::Mirror => {
+            let period = coordinate.rem_euclid(2 * size);
+            if period < size { period } else { 2 * size - 1 - period }
+        }
+        WrapMode::Clamp => coordinate.clamp(0, size - 1)
+    };
+
+    wrapped as usize
+}
+
+/// Weights of the 4 texels around a point, t being its offset past the second one
+fn catmull_rom_weights(t: f64) -> [f64; 4] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    [
+        0.5 * (-t3 + 2.0 * t2 - t),
+        0.5 * (3.0 * t3 - 5.0 * t2 + 2.0),
+        0.5 * (-3.0 * t3 + 4.0 * t2 + t),
+        0.5 * (t3 - t2)
+    ]
+}
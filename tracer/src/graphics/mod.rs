@@ -1,9 +1,26 @@
 pub mod bvh;
 pub mod aabb;
 pub mod texture;
+pub mod mipmap;
+pub mod texture_cache;
 pub mod material;
 pub mod light;
+pub mod ies;
+pub mod light_bvh;
 pub mod perlin;
+pub mod noise;
+pub mod procedural;
+pub mod texture_nodes;
+pub mod texture_expression;
+pub mod medium;
+pub mod normal_map;
+pub mod alpha_mask;
+pub mod layered;
+pub mod microfacet;
+pub mod sheen;
+pub mod merl;
+pub mod compositing;
+pub mod toon;
 mod camera;
 
-pub use camera::Camera;
+pub use camera::{Camera, Integrator};
@@ -0,0 +1,284 @@
+//! Seeded gradient noise.
+//!
+//! Ken Perlin's improved noise ("Improving Noise", 2002) and simplex noise, following
+//! Stefan Gustavson's "Simplex noise demystified", in 3D and 4D. Everything is
+//! evaluated from a fixed permutation table, without allocating, and the same seed
+//! always gives the same pattern.
+//!
+//! The fourth dimension is usually time: moving along it changes the pattern
+//! smoothly, so procedural textures can animate from frame to frame.
+
+use rand::{SeedableRng, rngs::StdRng, seq::SliceRandom};
+
+use crate::math::vec3::Point3;
+
+const F3: f64 = 1.0 / 3.0;
+const G3: f64 = 1.0 / 6.0;
+// (sqrt(5) - 1) / 4 and (5 - sqrt(5)) / 20
+const F4: f64 = 0.309_016_994_374_947_45;
+const G4: f64 = 0.138_196_601_125_010_5;
+
+
+#[derive(Clone, Copy)]
+pub struct Noise {
+    permutation: [u8; 512]  // a shuffle of 0..256, twice, so lookups don't need to wrap
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Self {
+        let mut shuffled: [u8; 256] = std::array::from_fn(|i| i as u8);
+        shuffled.shuffle(&mut StdRng::seed_from_u64(seed));
+
+        Noise { permutation: std::array::from_fn(|i| shuffled[i & 255]) }
+    }
+
+    fn hash(&self, index: usize) -> usize {
+        self.permutation[index] as usize
+    }
+
+    fn hash3(&self, x: usize, y: usize, z: usize) -> usize {
+        self.hash(self.hash(self.hash(x) + y) + z)
+    }
+
+    fn hash4(&self, x: usize, y: usize, z: usize, w: usize) -> usize {
+        self.hash(self.hash(self.hash(self.hash(x) + y) + z) + w)
+    }
+
+    /// Improved Perlin noise, roughly in [-1, 1]
+    pub fn perlin(&self, point: Point3) -> f64 {
+        let (x, y, z) = (point.x(), point.y(), point.z());
+        let (xi, yi, zi) = (lattice(x), lattice(y), lattice(z));
+        let (xf, yf, zf) = (x - x.floor(), y - y.floor(), z - z.floor());
+        let (u, v, w) = (fade(xf), fade(yf), fade(zf));
+
+        let mut sum = 0.0;
+        for corner in 0..8 {
+            let (dx, dy, dz) = (corner & 1, (corner >> 1) & 1, (corner >> 2) & 1);
+            let weight = blend(u, dx) * blend(v, dy) * blend(w, dz);
+            let hash = self.hash3(xi + dx, yi + dy, zi + dz);
+
+            sum += weight * gradient3(hash, xf - dx as f64, yf - dy as f64, zf - dz as f64);
+        }
+
+        sum
+    }
+
+    /// Improved Perlin noise over four dimensions, roughly in [-1, 1]
+    pub fn perlin_4d(&self, point: Point3, w: f64) -> f64 {
+        let coordinates = [point.x(), point.y(), point.z(), w];
+        let cells = coordinates.map(lattice);
+        let fractions = coordinates.map(|c| c - c.floor());
+        let fades = fractions.map(fade);
+
+        let mut sum = 0.0;
+        for corner in 0..16 {
+            let offsets: [usize; 4] = std::array::from_fn(|axis| (corner >> axis) & 1);
+            let weight: f64 = (0..4).map(|axis| blend(fades[axis], offsets[axis])).product();
+            let hash = self.hash4(
+                cells[0] + offsets[0], cells[1] + offsets[1], cells[2] + offsets[2], cells[3] + offsets[3]
+            );
+            let local: [f64; 4] = std::array::from_fn(|axis| fractions[axis] - offsets[axis] as f64);
+
+            sum += weight * gradient4(hash, local);
+        }
+
+        sum
+    }
+
+    /// Simplex noise, roughly in [-1, 1]. Cheaper than Perlin noise and without its
+    /// axis aligned artifacts.
+    pub fn simplex(&self, point: Point3) -> f64 {
+        let (x, y, z) = (point.x(), point.y(), point.z());
+
+        // Skew into the grid of simplices, and find the cell's origin
+        let skew = (x + y + z) * F3;
+        let (i, j, k) = ((x + skew).floor(), (y + skew).floor(), (z + skew).floor());
+        let unskew = (i + j + k) * G3;
+        let origin = [x - (i - unskew), y - (j - unskew), z - (k - unskew)];
+
+        // The simplex the point is in follows the order of its coordinates
+        let [x0, y0, z0] = origin;
+        let (first, second) = if x0 >= y0 {
+            if y0 >= z0 { ([1, 0, 0], [1, 1, 0]) }
+            else if x0 >= z0 { ([1, 0, 0], [1, 0, 1]) }
+            else { ([0, 0, 1], [1, 0, 1]) }
+        } else if y0 < z0 { ([0, 0, 1], [0, 1, 1]) }
+        else if x0 < z0 { ([0, 1, 0], [0, 1, 1]) }
+        else { ([0, 1, 0], [1, 1, 0]) };
+
+        let (ii, jj, kk) = (lattice(i), lattice(j), lattice(k));
+        let corners = [[0, 0, 0], first, second, [1, 1, 1]];
+
+        let mut sum = 0.0;
+        for (index, corner) in corners.iter().enumerate() {
+            let offset: [f64; 3] = std::array::from_fn(|axis| {
+                origin[axis] - corner[axis] as f64 + index as f64 * G3
+            });
+            let falloff = 0.6 - offset.iter().map(|c| c * c).sum::<f64>();
+            if falloff > 0.0 {
+                let hash = self.hash3(ii + corner[0], jj + corner[1], kk + corner[2]);
+                sum += falloff.powi(4) * gradient3(hash, offset[0], offset[1], offset[2]);
+            }
+        }
+
+        32.0 * sum
+    }
+
+    /// Simplex noise over four dimensions, roughly in [-1, 1]
+    pub fn simplex_4d(&self, point: Point3, w: f64) -> f64 {
+        let coordinates = [point.x(), point.y(), point.z(), w];
+
+        let skew = coordinates.iter().sum::<f64>() * F4;
+        let cell = coordinates.map(|c| (c + skew).floor());
+        let unskew = cell.iter().sum::<f64>() * G4;
+        let origin: [f64; 4] = std::array::from_fn(|axis| coordinates[axis] - (cell[axis] - unskew));
+
+        // Rank the coordinates, the simplex steps along the largest one first
+        let mut rank = [0; 4];
+        for a in 0..4 {
+            for b in a + 1..4 {
+                if origin[a] > origin[b] { rank[a] += 1 } else { rank[b] += 1 }
+            }
+        }
+
+        let cell = cell.map(lattice);
+        let mut sum = 0.0;
+        for step in 0..5 {
+            // Corner `step` includes the axes ranked at least 4 - step
+            let corner: [usize; 4] = std::array::from_fn(|axis| usize::from(step > 0 && rank[axis] >= 4 - step));
+            let offset: [f64; 4] = std::array::from_fn(|axis| {
+                origin[axis] - corner[axis] as f64 + step as f64 * G4
+            });
+            let falloff = 0.6 - offset.iter().map(|c| c * c).sum::<f64>();
+            if falloff > 0.0 {
+                let hash = self.hash4(
+                    cell[0] + corner[0], cell[1] + corner[1], cell[2] + corner[2], cell[3] + corner[3]
+                );
+                sum += falloff.powi(4) * gradient4(hash, offset);
+            }
+        }
+
+        27.0 * sum
+    }
+}
+
+/// Lattice coordinate wrapped to the permutation table
+fn lattice(coordinate: f64) -> usize {
+    (coordinate.floor() as i64 & 255) as usize
+}
+
+/// 6t^5 - 15t^4 + 10t^3, smooth up to the second derivative
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+/// Interpolation weight of the near (0) or far (1) corner along an axis
+fn blend(t: f64, corner: usize) -> f64 {
+    if corner == 0 { 1.0 - t } else { t }
+}
+
+/// Dot product with one of the 12 edge directions of a cube, picked by the hash
+fn gradient3(hash: usize, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 { y } else if h == 12 || h == 14 { x } else { z };
+
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+/// Dot product with one of the 32 edge directions of a tesseract, picked by the hash.
+/// One axis is left out, the other three are added with the signs from the hash.
+fn gradient4(hash: usize, offset: [f64; 4]) -> f64 {
+    let h = hash & 31;
+    let skipped_axis = h >> 3;
+
+    (0..4)
+        .filter(|&axis| axis != skipped_axis)
+        .enumerate()
+        .map(|(bit, axis)| if h & (1 << bit) == 0 { offset[axis] } else { -offset[axis] })
+        .sum()
+}
+
+
+#[derive(Clone, Copy)]
+pub enum NoiseBasis {
+    Perlin,
+    Simplex
+}
+
+/// Noise as used by procedural textures: a seeded noise, the basis function,
+/// and optionally the time to sample the animated 4D noise at.
+#[derive(Clone, Copy)]
+pub struct NoiseSource {
+    noise: Noise,
+    basis: NoiseBasis,
+    time: Option<f64>
+}
+
+impl NoiseSource {
+    pub fn new(seed: u64, basis: NoiseBasis) -> Self {
+        NoiseSource { noise: Noise::new(seed), basis, time: None }
+    }
+
+    /// The same noise, evolving along the fourth dimension with time
+    pub fn at_time(self, time: f64) -> Self {
+        NoiseSource { time: Some(time), ..self }
+    }
+
+    pub fn sample(&self, point: Point3) -> f64 {
+        match (self.basis, self.time) {
+            (NoiseBasis::Perlin, None) => self.noise.perlin(point),
+            (NoiseBasis::Perlin, Some(time)) => self.noise.perlin_4d(point, time),
+            (NoiseBasis::Simplex, None) => self.noise.simplex(point),
+            (NoiseBasis::Simplex, Some(time)) => self.noise.simplex_4d(point, time)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_points() -> impl Iterator<Item = Point3> {
+        (0..500).map(|i| {
+            let i = i as f64;
+            Point3::new(0.37 * i - 90.0, 13.0 * (0.1 * i).sin(), -0.71 * i + 0.5)
+        })
+    }
+
+    fn all_bases(noise: &Noise, point: Point3) -> [f64; 4] {
+        [noise.perlin(point), noise.perlin_4d(point, 0.3), noise.simplex(point), noise.simplex_4d(point, 0.3)]
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_noise() {
+        let (first, second) = (Noise::new(42), Noise::new(42));
+        for point in sample_points() {
+            assert_eq!(all_bases(&first, point), all_bases(&second, point));
+        }
+    }
+
+    #[test]
+    fn different_seeds_give_different_noise() {
+        let (first, second) = (Noise::new(1), Noise::new(2));
+        assert!(sample_points().any(|point| first.perlin(point) != second.perlin(point)));
+    }
+
+    #[test]
+    fn noise_stays_roughly_in_unit_range() {
+        let noise = Noise::new(7);
+        for point in sample_points() {
+            for value in all_bases(&noise, point) {
+                assert!(value.is_finite() && value.abs() <= 1.1, "{value} at {}, {}, {}", point.x(), point.y(), point.z());
+            }
+        }
+    }
+
+    #[test]
+    fn perlin_is_zero_on_the_lattice() {
+        let noise = Noise::new(3);
+        for (x, y, z) in [(0.0, 0.0, 0.0), (4.0, -2.0, 7.0), (-13.0, 5.0, -1.0)] {
+            assert_eq!(noise.perlin(Point3::new(x, y, z)), 0.0);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Normal and bump mapping.
+//!
+//! Wraps any material and perturbs the shading normal before the wrapped material
+//! scatters, so fine surface detail doesn't have to be modeled.
+//! The geometric normal of the hit record is left untouched.
+
+use std::sync::Arc;
+
+use crate::geometry::{Ray, hittable::HitRecord};
+use crate::math::vec3::{Color, Point3, Vec3};
+
+use super::{compositing::Compositing, material::{Material, MaterialSync}, texture::TextureSync};
+
+/// Step in texture coordinates used to estimate the height gradient of bump maps
+const BUMP_DELTA: f64 = 0.0005;
+
+
+pub enum NormalPerturbation {
+    /// Tangent space normal map. Color channels in [0, 1] encode a normal in [-1, 1],
+    /// red along the tangent, green along the bitangent and blue away from the surface.
+    /// strength scales the tilt away from the geometric normal.
+    NormalMap { map: Arc<TextureSync>, strength: f64 },
+    /// Grayscale height map. The surface is treated as if it were displaced along
+    /// its normal by height * scale.
+    BumpMap { height: Arc<TextureSync>, scale: f64 }
+}
+
+pub struct NormalMapped {
+    base: Arc<MaterialSync>,
+    perturbation: NormalPerturbation
+}
+
+impl NormalMapped {
+    pub fn new_normal_map(base: Arc<MaterialSync>, map: Arc<TextureSync>, strength: f64) -> Self {
+        NormalMapped { base, perturbation: NormalPerturbation::NormalMap { map, strength } }
+    }
+
+    pub fn new_bump_map(base: Arc<MaterialSync>, height: Arc<TextureSync>, scale: f64) -> Self {
+        NormalMapped { base, perturbation: NormalPerturbation::BumpMap { height, scale } }
+    }
+
+    /// Compute the perturbed shading normal at a hit point
+    pub fn perturbed_normal(&self, hitrec: &HitRecord) -> Vec3 {
+        match &self.perturbation {
+            NormalPerturbation::NormalMap { map, strength } => {
+                let encoded = map.value(hitrec.u, hitrec.v, &hitrec.point, &hitrec.geometric_normal);
+                let tangent_space = 2.0 * encoded - Color::new(1.0, 1.0, 1.0);
+
+                let normal = strength * tangent_space.x() * hitrec.tangent
+                           + strength * tangent_space.y() * hitrec.bitangent
+                           + tangent_space.z() * hitrec.normal;
+                if normal.near_zero() { hitrec.normal } else { normal.unit() }
+            }
+            NormalPerturbation::BumpMap { height, scale } => {
+                // Forward differences of the height along u and v.
+                // Procedural textures work on points, so the point is moved along as well.
+                let height_at = |u: f64, v: f64, point: Point3| {
+                    scale * height.scalar_value(u, v, &point, &hitrec.geometric_normal)
+                };
+                let base_height = height_at(hitrec.u, hitrec.v, hitrec.point);
+                let dhdu = (height_at(
+                    hitrec.u + BUMP_DELTA, hitrec.v, hitrec.point + BUMP_DELTA * hitrec.dpdu
+                ) - base_height) / BUMP_DELTA;
+                let dhdv = (height_at(
+                    hitrec.u, hitrec.v + BUMP_DELTA, hitrec.point + BUMP_DELTA * hitrec.dpdv
+                ) - base_height) / BUMP_DELTA;
+
+                // Partial derivatives of the displaced surface P'(u, v) = P(u, v) + h(u, v) * N
+                // Heights are always measured away from the outside of the surface.
+                let outward_normal = if hitrec.front_face { hitrec.normal } else { -hitrec.normal };
+                let dpdu = hitrec.dpdu + dhdu * outward_normal;
+                let dpdv = hitrec.dpdv + dhdv * outward_normal;
+
+                let normal = dpdu.cross(dpdv);
+                if normal.near_zero() {
+                    return hitrec.normal;
+                }
+
+                // The cross product follows the parametrization, not the side the ray is on
+                let normal = normal.unit();
+                if normal.dot(hitrec.normal) < 0.0 { -normal } else { normal }
+            }
+        }
+    }
+}
+
+impl Material for NormalMapped {
+    fn scatter(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<(Color, Ray)> {
+        let mut perturbed = hitrec.clone();
+        perturbed.set_shading_normal(self.perturbed_normal(hitrec));
+
+        self.base.scatter(incident_ray, &perturbed)
+    }
+
+    fn emitted(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Color {
+        self.base.emitted(incident_ray, hitrec)
+    }
+
+    fn eval(&self, incident_ray: &Ray, hitrec: &HitRecord, direction: Vec3) -> Option<Color> {
+        let mut perturbed = hitrec.clone();
+        perturbed.set_shading_normal(self.perturbed_normal(hitrec));
+
+        self.base.eval(incident_ray, &perturbed, direction)
+    }
+
+    fn opacity(&self, u: f64, v: f64, point: &Point3, normal: &Vec3) -> f64 {
+        self.base.opacity(u, v, point, normal)
+    }
+
+    fn compositing(&self, hitrec: &HitRecord) -> Compositing {
+        self.base.compositing(hitrec)
+    }
+
+    fn toon_shade(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<Color> {
+        let mut perturbed = hitrec.clone();
+        perturbed.set_shading_normal(self.perturbed_normal(hitrec));
+
+        self.base.toon_shade(incident_ray, &perturbed)
+    }
+}
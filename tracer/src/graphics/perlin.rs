@@ -1,4 +1,4 @@
-use rand::Rng;
+use rand::{Rng, SeedableRng, rngs::StdRng};
 
 use crate::math::vec3::{Point3, Vec3};
 
@@ -11,17 +11,18 @@ pub struct Perlin {
 }
 
 impl Perlin {
-    pub fn new(point_count: usize) -> Self {
-        let mut rv = Vec::<Vec3>::with_capacity(point_count);
-        for _ in 0..point_count {
-            rv.push(Vec3::random_range(-1.0, 1.0));
-        }
+    /// Same seed, same noise
+    pub fn new(point_count: usize, seed: u64) -> Self {
+        let rng = &mut StdRng::seed_from_u64(seed);
+        let rv = (0..point_count)
+            .map(|_| Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)))
+            .collect();
 
         Perlin { 
             random_vec: rv,
-            perm_x: Perlin::generate_perlin_permutation(point_count),
-            perm_y: Perlin::generate_perlin_permutation(point_count),
-            perm_z: Perlin::generate_perlin_permutation(point_count)
+            perm_x: Perlin::generate_perlin_permutation(point_count, rng),
+            perm_y: Perlin::generate_perlin_permutation(point_count, rng),
+            perm_z: Perlin::generate_perlin_permutation(point_count, rng)
         }
     }
 
@@ -33,16 +34,14 @@ impl Perlin {
         let i = point.x().floor() as i64;
         let j = point.y().floor() as i64;
         let k = point.z().floor() as i64;
-        let mut arr = vec![vec![vec![Vec3::default(); 2]; 2]; 2];
+        let point_count = self.random_vec.len() as i64;
+        let wrap = |index: i64| index.rem_euclid(point_count) as usize;
+        let mut arr = [[[Vec3::default(); 2]; 2]; 2];
 
         for di in 0..2 {
             for dj in 0..2 {
                 for dk in 0..2 {
-                    arr[di][dj][dk] = self.random_vec[
-                          self.perm_x[((i + di as i64) & 255) as usize]
-                        ^ self.perm_y[((j + dj as i64) & 255) as usize]
-                        ^ self.perm_z[((k + dk as i64) & 255) as usize]
-                    ]
+                    arr[di][dj][dk] = self.random_vec[self.hash(wrap(i + di as i64), wrap(j + dj as i64), wrap(k + dk as i64))];
                 }
             }
         }
@@ -50,6 +49,13 @@ impl Perlin {
         Perlin::perlin_interpolation(&arr, u, v, w)
     }
 
+    /// Chain the permutations, so the index stays below the point count whatever it is
+    fn hash(&self, i: usize, j: usize, k: usize) -> usize {
+        let point_count = self.random_vec.len();
+
+        self.perm_x[(self.perm_y[(self.perm_z[k] + j) % point_count] + i) % point_count]
+    }
+
     pub fn turbulence(&self, point: Point3, depth: usize) -> f64 {
         let mut accum = 0.0;
         let mut weight = 1.0;
@@ -64,7 +70,7 @@ impl Perlin {
         accum.abs()
     }
 
-    fn perlin_interpolation(arr: &Vec<Vec<Vec<Vec3>>>, u: f64, v: f64, w: f64) -> f64 {
+    fn perlin_interpolation(arr: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
         let uu = u * u * (3.0 - 2.0 * u);
         let vv = v * v * (3.0 - 2.0 * v);
         let ww = w * w * (3.0 - 2.0 * w);
@@ -90,20 +96,18 @@ impl Perlin {
         accum
     }
 
-    fn generate_perlin_permutation(point_count: usize) -> Vec<usize> {
+    fn generate_perlin_permutation(point_count: usize, rng: &mut impl Rng) -> Vec<usize> {
         let mut perm = Vec::<usize>::with_capacity(point_count);
 
         for i in 0..point_count {
-            perm.push(i as usize);
+            perm.push(i);
         }
 
-        Perlin::permute(&mut perm);
+        Perlin::permute(&mut perm, rng);
         perm
     }
 
-    fn permute(array: &mut Vec<usize>) {
-        let mut rng = rand::thread_rng();
-
+    fn permute(array: &mut [usize], rng: &mut impl Rng) {
         for i in (1..array.len()).rev() {
             let target = rng.gen_range(0..i);
             array.swap(i, target);
@@ -113,17 +117,38 @@ impl Perlin {
 
 impl Default for Perlin {
     fn default() -> Self {
-        let point_count = 256;
-        let mut rv = Vec::<Vec3>::with_capacity(point_count);
-        for _ in 0..point_count {
-            rv.push(Vec3::random_range(-1.0, 1.0));
+        Perlin::new(256, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_points() -> impl Iterator<Item = Point3> {
+        (0..200).map(|i| {
+            let i = i as f64;
+            Point3::new(0.37 * i - 30.0, 1.13 * i.sin() * 50.0, -0.71 * i)
+        })
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_noise() {
+        let (first, second) = (Perlin::new(256, 7), Perlin::new(256, 7));
+        for point in sample_points() {
+            assert_eq!(first.noise(&point), second.noise(&point));
         }
+    }
 
-        Perlin { 
-            random_vec: rv,
-            perm_x: Perlin::generate_perlin_permutation(point_count),
-            perm_y: Perlin::generate_perlin_permutation(point_count),
-            perm_z: Perlin::generate_perlin_permutation(point_count)
+    #[test]
+    fn any_point_count_stays_in_range() {
+        for point_count in [1, 3, 100, 256, 257] {
+            let perlin = Perlin::new(point_count, 1);
+            for point in sample_points() {
+                let noise = perlin.noise(&point);
+                // Gradients are at most sqrt(3) long, and at most sqrt(3) away from the corners
+                assert!(noise.is_finite() && noise.abs() <= 3.0, "{noise} with {point_count} points");
+            }
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,336 @@
+//! Parameterized procedural patterns.
+//!
+//! Most of these are scalar patterns, returned as a gray color in [0, 1].
+//! Noise based ones can be reseeded, switched to simplex noise, or animated,
+//! by replacing their noise source.
+//! Run them through a ColorRamp to color them, e.g. a ridged multifractal through
+//! a ramp from grass to rock to snow.
+
+use std::sync::Arc;
+
+use crate::math::vec3::{Point3, Vec3, Color};
+
+use super::{noise::{NoiseSource, NoiseBasis}, texture::{Texture, TextureSync}};
+
+
+/// Fractal Brownian motion, octaves of noise summed with growing frequency and
+/// shrinking amplitude. Roughly in [-1, 1].
+fn fbm(noise: &NoiseSource, point: Point3, octaves: usize, lacunarity: f64, gain: f64) -> f64 {
+    let mut sum = 0.0;
+    let mut amplitude = 1.0;
+    let mut total_amplitude = 0.0;
+    let mut point = point;
+
+    for _ in 0..octaves {
+        sum += amplitude * noise.sample(point);
+        total_amplitude += amplitude;
+        amplitude *= gain;
+        point *= lacunarity;
+    }
+
+    if total_amplitude > 0.0 { sum / total_amplitude } else { 0.0 }
+}
+
+fn gray(value: f64) -> Color {
+    let value = value.clamp(0.0, 1.0);
+    Color::new(value, value, value)
+}
+
+
+// fBm
+// ===
+
+pub struct FbmTexture {
+    pub noise: NoiseSource,     // seed, basis and time of the noise, Perlin noise by default
+    scale: f64,         // frequency of the first octave
+    octaves: usize,
+    lacunarity: f64,    // frequency multiplier between octaves, usually 2
+    gain: f64           // amplitude multiplier between octaves, usually 0.5
+}
+
+impl FbmTexture {
+    /// Same seed, same noise
+    pub fn new(scale: f64, octaves: usize, lacunarity: f64, gain: f64, seed: u64) -> Self {
+        FbmTexture { noise: NoiseSource::new(seed, NoiseBasis::Perlin), scale, octaves, lacunarity, gain }
+    }
+}
+
+impl Texture for FbmTexture {
+    fn value(&self, _: f64, _: f64, point: &Point3, _: &Vec3) -> Color {
+        let value = fbm(&self.noise, self.scale * *point, self.octaves, self.lacunarity, self.gain);
+        gray(0.5 + 0.5 * value)
+    }
+}
+
+
+// Ridged multifractal
+// ===================
+
+/// Musgrave's ridged multifractal. Sharp ridges where the noise crosses zero,
+/// and octaves only add detail on top of the ridges, leaving the valleys smooth.
+pub struct RidgedTexture {
+    pub noise: NoiseSource,
+    scale: f64,
+    octaves: usize,
+    lacunarity: f64,
+    gain: f64,      // how strongly ridges of an octave let the next one through
+    offset: f64     // height of the ridges, usually 1
+}
+
+impl RidgedTexture {
+    pub fn new(scale: f64, octaves: usize, lacunarity: f64, gain: f64, offset: f64, seed: u64) -> Self {
+        RidgedTexture { noise: NoiseSource::new(seed, NoiseBasis::Perlin), scale, octaves, lacunarity, gain, offset }
+    }
+}
+
+impl Texture for RidgedTexture {
+    fn value(&self, _: f64, _: f64, point: &Point3, _: &Vec3) -> Color {
+        let mut point = self.scale * *point;
+        let mut sum = 0.0;
+        let mut amplitude = 1.0;
+        let mut total_amplitude = 0.0;
+        let mut weight = 1.0;
+
+        for _ in 0..self.octaves {
+            let ridge = (self.offset - self.noise.sample(point).abs()).powi(2) * weight;
+            weight = (ridge * self.gain).clamp(0.0, 1.0);
+
+            sum += amplitude * ridge;
+            total_amplitude += amplitude * self.offset * self.offset;
+            amplitude /= self.lacunarity;
+            point *= self.lacunarity;
+        }
+
+        gray(if total_amplitude > 0.0 { sum / total_amplitude } else { 0.0 })
+    }
+}
+
+
+// Worley
+// ======
+
+#[derive(Clone, Copy)]
+pub enum DistanceMetric {
+    Euclidean,
+    Manhattan,
+    Chebyshev
+}
+
+impl DistanceMetric {
+    fn distance(&self, offset: Vec3) -> f64 {
+        match self {
+            DistanceMetric::Euclidean => offset.length(),
+            DistanceMetric::Manhattan => offset.x().abs() + offset.y().abs() + offset.z().abs(),
+            DistanceMetric::Chebyshev => offset.x().abs().max(offset.y().abs()).max(offset.z().abs())
+        }
+    }
+}
+
+/// Which distances to the feature points make up the pattern
+#[derive(Clone, Copy)]
+pub enum WorleyFeature {
+    /// Distance to the closest point, round cells
+    F1,
+    /// Distance to the second closest point
+    F2,
+    /// Difference of both, thin lines along the cell borders
+    F2MinusF1
+}
+
+/// Cellular noise: one randomly placed feature point per unit cell,
+/// shaded by the distance to the closest ones.
+pub struct WorleyTexture {
+    scale: f64,
+    metric: DistanceMetric,
+    feature: WorleyFeature,
+    jitter: f64,    // how far feature points may stray from cell centers, in [0, 1]
+    seed: u32
+}
+
+impl WorleyTexture {
+    /// Same seed, same feature points
+    pub fn new(scale: f64, metric: DistanceMetric, feature: WorleyFeature, jitter: f64, seed: u32) -> Self {
+        WorleyTexture { scale, metric, feature, jitter: jitter.clamp(0.0, 1.0), seed }
+    }
+
+    /// Distances to the closest and second closest feature points
+    fn closest_distances(&self, point: Point3) -> (f64, f64) {
+        let cell = (point.x().floor() as i64, point.y().floor() as i64, point.z().floor() as i64);
+        let mut closest = (f64::INFINITY, f64::INFINITY);
+
+        for di in -1..=1 {
+            for dj in -1..=1 {
+                for dk in -1..=1 {
+                    let neighbour = (cell.0 + di, cell.1 + dj, cell.2 + dk);
+                    let feature_point = Point3::new(neighbour.0 as f64, neighbour.1 as f64, neighbour.2 as f64)
+                        + Vec3::new(0.5, 0.5, 0.5)
+                        + self.jitter * (cell_random(neighbour, self.seed) - Vec3::new(0.5, 0.5, 0.5));
+
+                    let distance = self.metric.distance(feature_point - point);
+                    if distance < closest.0 {
+                        closest = (distance, closest.0);
+                    } else if distance < closest.1 {
+                        closest.1 = distance;
+                    }
+                }
+            }
+        }
+
+        closest
+    }
+}
+
+impl Texture for WorleyTexture {
+    fn value(&self, _: f64, _: f64, point: &Point3, _: &Vec3) -> Color {
+        let (f1, f2) = self.closest_distances(self.scale * *point);
+
+        gray(match self.feature {
+            WorleyFeature::F1 => f1,
+            WorleyFeature::F2 => f2,
+            WorleyFeature::F2MinusF1 => f2 - f1
+        })
+    }
+}
+
+/// A random point in the unit cube that only depends on the cell and the seed
+fn cell_random(cell: (i64, i64, i64), seed: u32) -> Vec3 {
+    let hash = |salt: u32| {
+        let mut h = seed ^ salt.wrapping_mul(0x9E37_79B9);
+        for coordinate in [cell.0, cell.1, cell.2] {
+            h ^= coordinate as u32;
+            h = h.wrapping_mul(0x85EB_CA6B);
+            h ^= h >> 13;
+            h = h.wrapping_mul(0xC2B2_AE35);
+            h ^= h >> 16;
+        }
+        h as f64 / u32::MAX as f64
+    };
+
+    Vec3::new(hash(1), hash(2), hash(3))
+}
+
+
+// Wood
+// ====
+
+/// Concentric growth rings around the Y axis, wobbled by noise
+pub struct WoodTexture {
+    pub noise: NoiseSource,
+    light: Color,
+    dark: Color,
+    rings_per_unit: f64,
+    distortion: f64     // how much noise bends the rings, in rings
+}
+
+impl WoodTexture {
+    pub fn new(light: Color, dark: Color, rings_per_unit: f64, distortion: f64, seed: u64) -> Self {
+        WoodTexture { noise: NoiseSource::new(seed, NoiseBasis::Perlin), light, dark, rings_per_unit, distortion }
+    }
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, _: f64, _: f64, point: &Point3, _: &Vec3) -> Color {
+        let radius = point.x().hypot(point.z());
+        let wobble = fbm(&self.noise, *point * 2.0, 4, 2.0, 0.5);
+        let ring = (radius * self.rings_per_unit + self.distortion * wobble).rem_euclid(1.0);
+
+        // Early wood grows slowly lighter, late wood turns dark quickly
+        let darkness = ring.powi(3);
+        (1.0 - darkness) * self.light + darkness * self.dark
+    }
+}
+
+
+// Marble
+// ======
+
+/// Veins from a sine wave along X, bent by fBm
+pub struct MarbleTexture {
+    pub noise: NoiseSource,
+    scale: f64,
+    turbulence: f64,
+    octaves: usize
+}
+
+impl MarbleTexture {
+    pub fn new(scale: f64, turbulence: f64, octaves: usize, seed: u64) -> Self {
+        MarbleTexture { noise: NoiseSource::new(seed, NoiseBasis::Perlin), scale, turbulence, octaves }
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, _: f64, _: f64, point: &Point3, _: &Vec3) -> Color {
+        let bend = fbm(&self.noise, self.scale * *point, self.octaves, 2.0, 0.5);
+        gray(0.5 + 0.5 * (self.scale * point.x() + self.turbulence * bend).sin())
+    }
+}
+
+
+// Gradient
+// ========
+
+/// Linear ramp from 0 at one point to 1 at another, clamped past both
+pub struct GradientTexture {
+    start: Point3,
+    end: Point3
+}
+
+impl GradientTexture {
+    pub fn new(start: Point3, end: Point3) -> Self {
+        GradientTexture { start, end }
+    }
+}
+
+impl Texture for GradientTexture {
+    fn value(&self, _: f64, _: f64, point: &Point3, _: &Vec3) -> Color {
+        let axis = self.end - self.start;
+        let length_squared = axis.length_squared();
+        if length_squared == 0.0 {
+            return gray(0.0);
+        }
+
+        gray((*point - self.start).dot(axis) / length_squared)
+    }
+}
+
+
+// Color ramp
+// ==========
+
+/// Maps a scalar texture through gradient stops, interpolating linearly between them.
+/// Values before the first stop or after the last one take that stop's color.
+pub struct ColorRamp {
+    input: Arc<TextureSync>,
+    stops: Vec<(f64, Color)>
+}
+
+impl ColorRamp {
+    pub fn new(input: Arc<TextureSync>, stops: &[(f64, Color)]) -> Self {
+        let mut stops = stops.to_vec();
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        ColorRamp { input, stops }
+    }
+}
+
+impl Texture for ColorRamp {
+    fn value(&self, u: f64, v: f64, point: &Point3, normal: &Vec3) -> Color {
+        let Some(&(first_position, first_color)) = self.stops.first() else {
+            return gray(0.0);
+        };
+        let value = self.input.scalar_value(u, v, point, normal);
+        if value <= first_position {
+            return first_color;
+        }
+
+        for pair in self.stops.windows(2) {
+            let ((start, start_color), (end, end_color)) = (pair[0], pair[1]);
+            if value <= end {
+                let t = if end > start { (value - start) / (end - start) } else { 1.0 };
+                return (1.0 - t) * start_color + t * end_color;
+            }
+        }
+
+        self.stops[self.stops.len() - 1].1
+    }
+}
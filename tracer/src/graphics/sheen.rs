@@ -0,0 +1,183 @@
+//! Cloth and velvet sheen.
+//!
+//! The sheen lobe is the "Charlie" microfacet distribution from
+//! "Production Friendly Microfacet Sheen BRDF" (Estevez & Kulla 2017),
+//! with Neubelt & Pettineo's approximate visibility term.
+//! It's bright at grazing angles, like the fibers sticking out of fabric.
+
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use rand::random;
+
+use crate::geometry::{Ray, hittable::HitRecord};
+use crate::math::{utils::get_random_point_on_unit_sphere, vec3::{Color, Point3, Vec3}};
+use crate::rendering::color::{COLOR_BLACK, COLOR_WHITE};
+
+use super::{compositing::Compositing, material::{Material, MaterialSync}};
+
+/// Resolution of the precomputed directional albedo of the sheen lobe
+const ALBEDO_TABLE_SIZE: usize = 32;
+
+
+pub struct Sheen {
+    base: Option<Arc<MaterialSync>>,
+    color: Color,
+    roughness: f64,
+    albedo_table: Vec<f64>  // directional albedo of the lobe, indexed by the view cosine
+}
+
+impl Sheen {
+    /// Sheen on its own, like velvet
+    pub fn new(color: Color, roughness: f64) -> Self {
+        Sheen::new_with_base(None, color, roughness)
+    }
+
+    /// Sheen layered on top of another material, usually a diffuse one.
+    /// The base gets whatever energy the sheen doesn't reflect.
+    pub fn new_layer(base: Arc<MaterialSync>, color: Color, roughness: f64) -> Self {
+        Sheen::new_with_base(Some(base), color, roughness)
+    }
+
+    fn new_with_base(base: Option<Arc<MaterialSync>>, color: Color, roughness: f64) -> Self {
+        let roughness = roughness.clamp(0.01, 1.0);
+        let mut sheen = Sheen { base, color, roughness, albedo_table: Vec::new() };
+        sheen.albedo_table = (0..ALBEDO_TABLE_SIZE)
+            .map(|i| sheen.directional_albedo((i as f64 + 0.5) / ALBEDO_TABLE_SIZE as f64))
+            .collect();
+
+        sheen
+    }
+
+    /// Charlie distribution of microfacet normals, by the cosine of their angle to the normal
+    fn distribution(&self, cos_h: f64) -> f64 {
+        let inv_roughness = 1.0 / self.roughness;
+        let sin2_h = (1.0 - cos_h * cos_h).max(0.0);
+
+        (2.0 + inv_roughness) * sin2_h.powf(inv_roughness / 2.0) / (2.0 * PI)
+    }
+
+    /// Neubelt & Pettineo visibility, folds in the 1 / (4 cos_o cos_i) of microfacet BRDFs
+    fn visibility(cos_o: f64, cos_i: f64) -> f64 {
+        1.0 / (4.0 * (cos_i + cos_o - cos_i * cos_o))
+    }
+
+    /// The uncolored sheen BRDF times cos_i
+    fn lobe(&self, cos_o: f64, cos_i: f64, cos_h: f64) -> f64 {
+        self.distribution(cos_h) * Sheen::visibility(cos_o, cos_i) * cos_i
+    }
+
+    /// Integrate the lobe over the hemisphere numerically for a view cosine
+    fn directional_albedo(&self, cos_o: f64) -> f64 {
+        let steps = 64;
+        let sin_o = (1.0 - cos_o * cos_o).sqrt();
+        let mut albedo = 0.0;
+
+        for i in 0..steps {
+            let theta = (i as f64 + 0.5) / steps as f64 * PI / 2.0;
+            let (sin_i, cos_i) = theta.sin_cos();
+            for j in 0..steps {
+                let phi = (j as f64 + 0.5) / steps as f64 * 2.0 * PI;
+                // Half vector between (sin_o, 0, cos_o) and (sin_i cos(phi), sin_i sin(phi), cos_i)
+                let half = (sin_o + sin_i * phi.cos(), sin_i * phi.sin(), cos_o + cos_i);
+                let cos_h = half.2 / (half.0 * half.0 + half.1 * half.1 + half.2 * half.2).sqrt();
+
+                albedo += self.lobe(cos_o, cos_i, cos_h) * sin_i;
+            }
+        }
+
+        albedo * (PI / 2.0 / steps as f64) * (2.0 * PI / steps as f64)
+    }
+
+    fn lookup_albedo(&self, cos_o: f64) -> f64 {
+        let index = (cos_o * ALBEDO_TABLE_SIZE as f64) as usize;
+        self.albedo_table[index.min(ALBEDO_TABLE_SIZE - 1)]
+    }
+
+    /// Cosine weighted sample of the sheen lobe, returns the weight and the direction
+    fn sample_lobe(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<(Color, Ray)> {
+        let direction = (hitrec.normal + get_random_point_on_unit_sphere()).unit();
+        let view = -incident_ray.direction.unit();
+
+        let cos_o = view.dot(hitrec.normal);
+        let cos_i = direction.dot(hitrec.normal);
+        if cos_o <= 0.0 || cos_i <= 0.0 {
+            return None;
+        }
+        let cos_h = (view + direction).unit().dot(hitrec.normal);
+
+        // pdf of cosine sampling is cos_i / PI
+        let weight = self.lobe(cos_o, cos_i, cos_h) * PI / cos_i;
+        Some((self.color * weight, incident_ray.spawn(hitrec.point, direction)))
+    }
+}
+
+impl Material for Sheen {
+    fn scatter(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<(Color, Ray)> {
+        let base = match &self.base {
+            Some(base) => base,
+            None => { return self.sample_lobe(incident_ray, hitrec); }
+        };
+
+        // Pick a layer with equal chance, and leave to the base what the sheen doesn't reflect
+        if random::<f64>() < 0.5 {
+            let (attenuation, scattered) = self.sample_lobe(incident_ray, hitrec)?;
+            return Some((2.0 * attenuation, scattered));
+        }
+
+        let cos_o = (-incident_ray.direction.unit().dot(hitrec.normal)).max(0.0);
+        let base_weight = COLOR_WHITE - self.color * self.lookup_albedo(cos_o);
+        let (attenuation, scattered) = base.scatter(incident_ray, hitrec)?;
+
+        Some((2.0 * attenuation * base_weight, scattered))
+    }
+
+    fn emitted(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Color {
+        match &self.base {
+            Some(base) => base.emitted(incident_ray, hitrec),
+            None => Color::zero()
+        }
+    }
+
+    fn opacity(&self, u: f64, v: f64, point: &Point3, normal: &Vec3) -> f64 {
+        match &self.base {
+            Some(base) => base.opacity(u, v, point, normal),
+            None => 1.0
+        }
+    }
+
+    /// The lobe, plus the base weighted like in scatter(). Only if the base can be evaluated.
+    fn eval(&self, incident_ray: &Ray, hitrec: &HitRecord, direction: Vec3) -> Option<Color> {
+        let view = -incident_ray.direction.unit();
+        let direction = direction.unit();
+        let cos_o = view.dot(hitrec.normal);
+        let cos_i = direction.dot(hitrec.normal);
+
+        let sheen = if cos_o > 0.0 && cos_i > 0.0 {
+            let cos_h = (view + direction).unit().dot(hitrec.normal);
+            self.color * self.lobe(cos_o, cos_i, cos_h)
+        } else {
+            COLOR_BLACK
+        };
+
+        match &self.base {
+            Some(base) => {
+                let base_weight = COLOR_WHITE - self.color * self.lookup_albedo(cos_o.max(0.0));
+                Some(sheen + base_weight * base.eval(incident_ray, hitrec, direction)?)
+            }
+            None => Some(sheen)
+        }
+    }
+
+    fn compositing(&self, hitrec: &HitRecord) -> Compositing {
+        match &self.base {
+            Some(base) => base.compositing(hitrec),
+            None => Compositing::Opaque
+        }
+    }
+
+    /// The base's flat shading, cel shading has no use for the sheen
+    fn toon_shade(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<Color> {
+        self.base.as_ref().and_then(|base| base.toon_shade(incident_ray, hitrec))
+    }
+}
@@ -1,20 +1,58 @@
 use std::sync::Arc;
 
-use image::GenericImageView;
+use crate::math::vec3::{Point3, Color, Vec3};
+use crate::rendering::color::{COLOR_BLACK, COLOR_WHITE, ColorSpace};
 
-use crate::math::interval::Interval;
-use crate::math::vec3::{Point3, Color};
-use crate::rendering::color::{COLOR_BLACK, COLOR_WHITE};
-
-use super::perlin::Perlin;
+use super::{perlin::Perlin, mipmap::{MipMap, FilterMode, WrapMode}, texture_cache::TextureCache};
 
 
 pub trait Texture {
-    fn value(&self, u: f64, v: f64, point: &Point3) -> Color;
+    fn value(&self, u: f64, v: f64, point: &Point3, normal: &Vec3) -> Color;
+
+    /// Grayscale value of the texture, for textures used as masks, heights, etc.
+    fn scalar_value(&self, u: f64, v: f64, point: &Point3, normal: &Vec3) -> f64 {
+        let color = self.value(u, v, point, normal);
+        (color.x() + color.y() + color.z()) / 3.0
+    }
+
+    /// Value averaged over the area a pixel covers, for textures that can filter themselves
+    #[allow(unused_variables)]
+    fn value_footprint(&self, u: f64, v: f64, point: &Point3, normal: &Vec3, footprint: &Footprint) -> Color {
+        self.value(u, v, point, normal)
+    }
+
+    /// Grayscale value averaged over the area a pixel covers
+    fn scalar_value_footprint(&self, u: f64, v: f64, point: &Point3, normal: &Vec3, footprint: &Footprint) -> f64 {
+        let color = self.value_footprint(u, v, point, normal, footprint);
+        (color.x() + color.y() + color.z()) / 3.0
+    }
 }
 
 pub type TextureSync = dyn Texture + Send + Sync;
 
+/// How much the hit point and the texture coordinates change from one pixel
+/// to the next, along the image's x and y. All zero when unknown.
+#[derive(Clone, Copy, Default)]
+pub struct Footprint {
+    pub dpdx: Vec3,
+    pub dpdy: Vec3,
+    pub dudx: f64,
+    pub dvdx: f64,
+    pub dudy: f64,
+    pub dvdy: f64
+}
+
+impl Footprint {
+    /// Width of the footprint in texels of an image of the given size
+    pub fn texel_width(&self, width: usize, height: usize) -> f64 {
+        let (width, height) = (width as f64, height as f64);
+        let along_x = (self.dudx * width).hypot(self.dvdx * height);
+        let along_y = (self.dudy * width).hypot(self.dvdy * height);
+
+        along_x.max(along_y)
+    }
+}
+
 
 // Solid Color
 // ===========
@@ -35,7 +73,7 @@ impl SolidColorTexture {
 }
 
 impl Texture for SolidColorTexture {
-    fn value(&self, _: f64, _: f64, _: &Point3) -> Color {
+    fn value(&self, _: f64, _: f64, _: &Point3, _: &Vec3) -> Color {
         return self.value;
     }
 }
@@ -75,52 +113,272 @@ impl CheckerTexture {
 }
 
 impl Texture for CheckerTexture {
-    fn value(&self, u: f64, v: f64, point: &Point3) -> Color {
+    fn value(&self, u: f64, v: f64, point: &Point3, normal: &Vec3) -> Color {
         let x_int = (self.inv_scale * (point.x())).floor() as i64;
         let y_int = (self.inv_scale * (point.y())).floor() as i64;
         let z_int = (self.inv_scale * (point.z())).floor() as i64;
 
         if (x_int + y_int + z_int) % 2 == 0 {
-            return self.even.value(u, v, point);
+            return self.even.value(u, v, point, normal);
+        } else {
+            return self.odd.value(u, v, point, normal);
+        }
+
+    }
+
+    /// Fade to the average of both colors once a pixel covers about a whole check,
+    /// instead of aliasing into moire patterns in the distance.
+    fn value_footprint(&self, u: f64, v: f64, point: &Point3, normal: &Vec3, footprint: &Footprint) -> Color {
+        let fade = pattern_fade(self.inv_scale * footprint.dpdx.length().max(footprint.dpdy.length()));
+        if fade == 0.0 {
+            return self.value(u, v, point, normal);
+        }
+
+        let average = 0.5 * (
+            self.even.value_footprint(u, v, point, normal, footprint) + self.odd.value_footprint(u, v, point, normal, footprint)
+        );
+        (1.0 - fade) * self.value(u, v, point, normal) + fade * average
+    }
+}
+
+/// How much of a repeating pattern to replace by its average, given how many
+/// repetitions a pixel covers. Starts fading at half a repetition per pixel.
+fn pattern_fade(cells_per_pixel: f64) -> f64 {
+    ((cells_per_pixel - 0.5) / 0.5).clamp(0.0, 1.0)
+}
+
+/// Cells of a pattern in texture space a pixel covers, along the widest axis
+fn cells_per_pixel(footprint: &Footprint, columns: f64, rows: f64) -> f64 {
+    let along_x = (footprint.dudx * columns).hypot(footprint.dvdx * rows);
+    let along_y = (footprint.dudy * columns).hypot(footprint.dvdy * rows);
+
+    along_x.max(along_y)
+}
+
+
+// UV Checker
+// ==========
+
+/// Checkers laid out in texture coordinates, so they stick to the surface
+pub struct UvCheckerTexture {
+    columns: f64,   // checks along u
+    rows: f64,      // checks along v
+    even: Arc<TextureSync>,
+    odd: Arc<TextureSync>
+}
+
+impl UvCheckerTexture {
+    pub fn new(columns: f64, rows: f64, even: Arc<TextureSync>, odd: Arc<TextureSync>) -> Self {
+        UvCheckerTexture { columns, rows, even, odd }
+    }
+
+    pub fn new_color(columns: f64, rows: f64, even: Color, odd: Color) -> Self {
+        UvCheckerTexture::new(
+            columns,
+            rows,
+            Arc::new(SolidColorTexture::new(even)),
+            Arc::new(SolidColorTexture::new(odd))
+        )
+    }
+}
+
+impl Texture for UvCheckerTexture {
+    fn value(&self, u: f64, v: f64, point: &Point3, normal: &Vec3) -> Color {
+        let column = (u * self.columns).floor() as i64;
+        let row = (v * self.rows).floor() as i64;
+
+        if (column + row).rem_euclid(2) == 0 {
+            self.even.value(u, v, point, normal)
         } else {
-            return self.odd.value(u, v, point);
+            self.odd.value(u, v, point, normal)
+        }
+    }
+
+    fn value_footprint(&self, u: f64, v: f64, point: &Point3, normal: &Vec3, footprint: &Footprint) -> Color {
+        let fade = pattern_fade(cells_per_pixel(footprint, self.columns, self.rows));
+        if fade == 0.0 {
+            return self.value(u, v, point, normal);
         }
 
+        let average = 0.5 * (
+            self.even.value_footprint(u, v, point, normal, footprint) + self.odd.value_footprint(u, v, point, normal, footprint)
+        );
+        (1.0 - fade) * self.value(u, v, point, normal) + fade * average
+    }
+}
+
+
+// UV Grid
+// =======
+
+/// Grid lines over a fill in texture coordinates, e.g. to check how UVs stretch
+pub struct UvGridTexture {
+    columns: f64,
+    rows: f64,
+    line_width: f64,    // fraction of a cell covered by its lines, in [0, 1]
+    line: Color,
+    fill: Color
+}
+
+impl UvGridTexture {
+    pub fn new(columns: f64, rows: f64, line_width: f64, line: Color, fill: Color) -> Self {
+        UvGridTexture { columns, rows, line_width: line_width.clamp(0.0, 1.0), line, fill }
+    }
+}
+
+impl Texture for UvGridTexture {
+    fn value(&self, u: f64, v: f64, _: &Point3, _: &Vec3) -> Color {
+        // Lines are centered on the cell borders
+        let half_width = 0.5 * self.line_width;
+        let on_line = |coordinate: f64| {
+            let cell_position = coordinate.rem_euclid(1.0);
+            cell_position < half_width || cell_position > 1.0 - half_width
+        };
+
+        if on_line(u * self.columns) || on_line(v * self.rows) { self.line } else { self.fill }
+    }
+
+    fn value_footprint(&self, u: f64, v: f64, point: &Point3, normal: &Vec3, footprint: &Footprint) -> Color {
+        let fade = pattern_fade(cells_per_pixel(footprint, self.columns, self.rows));
+        if fade == 0.0 {
+            return self.value(u, v, point, normal);
+        }
+
+        let coverage = 1.0 - (1.0 - self.line_width).powi(2);
+        let average = coverage * self.line + (1.0 - coverage) * self.fill;
+        (1.0 - fade) * self.value(u, v, point, normal) + fade * average
+    }
+}
+
+
+// Triplanar
+// =========
+
+/// Projects a texture along the X, Y and Z axes and blends the three by the surface normal.
+/// Gives surfaces without usable texture coordinates a texture that doesn't stretch.
+pub struct TriplanarTexture {
+    texture: Arc<TextureSync>,
+    scale: f64,         // texture repetitions per unit of world space
+    sharpness: f64      // higher values narrow the blend between projections
+}
+
+impl TriplanarTexture {
+    pub fn new(texture: Arc<TextureSync>, scale: f64, sharpness: f64) -> Self {
+        TriplanarTexture { texture, scale, sharpness }
+    }
+
+    /// Weight of the projections along X, Y and Z.
+    /// Without a normal, e.g. inside volumes, all three are blended evenly.
+    fn weights(&self, normal: &Vec3) -> [f64; 3] {
+        let weights = [normal.x(), normal.y(), normal.z()].map(|n| n.abs().powf(self.sharpness));
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return [1.0 / 3.0; 3];
+        }
+
+        weights.map(|weight| weight / total)
+    }
+
+    /// Texture coordinates of the projection along an axis, from a point or a change of point
+    fn project(&self, axis: usize, point: Vec3) -> (f64, f64) {
+        match axis {
+            0 => (self.scale * point.z(), self.scale * point.y()),
+            1 => (self.scale * point.x(), self.scale * point.z()),
+            _ => (self.scale * point.x(), self.scale * point.y())
+        }
+    }
+}
+
+impl Texture for TriplanarTexture {
+    fn value(&self, _: f64, _: f64, point: &Point3, normal: &Vec3) -> Color {
+        let mut color = Color::zero();
+        for (axis, weight) in self.weights(normal).into_iter().enumerate() {
+            if weight > 0.0 {
+                let (u, v) = self.project(axis, *point);
+                color += weight * self.texture.value(u, v, point, normal);
+            }
+        }
+
+        color
+    }
+
+    fn value_footprint(&self, _: f64, _: f64, point: &Point3, normal: &Vec3, footprint: &Footprint) -> Color {
+        let mut color = Color::zero();
+        for (axis, weight) in self.weights(normal).into_iter().enumerate() {
+            if weight > 0.0 {
+                let (u, v) = self.project(axis, *point);
+                let (dudx, dvdx) = self.project(axis, footprint.dpdx);
+                let (dudy, dvdy) = self.project(axis, footprint.dpdy);
+                let projected = Footprint { dudx, dvdx, dudy, dvdy, ..*footprint };
+
+                color += weight * self.texture.value_footprint(u, v, point, normal, &projected);
+            }
+        }
+
+        color
     }
 }
 
 // Image Texture
 // =============
 pub struct ImageTexture {
-    image: image::DynamicImage
+    mipmap: MipMap
 }
 
 impl ImageTexture {
-    pub fn new(path: &str) -> ImageTexture {
-        ImageTexture { image: image::open(path).unwrap() }
+    /// Bilinear filtering, clamped at the edges
+    pub fn new(path: &str, color_space: ColorSpace) -> ImageTexture {
+        ImageTexture::new_filtered(path, color_space, FilterMode::Bilinear, WrapMode::Clamp, WrapMode::Clamp)
     }
-}
 
-impl Texture for ImageTexture {
-    fn value(&self, u: f64, v: f64, _: &Point3) -> Color {
-        if self.image.height() <= 0 {
-            return Color::new(0.0, 1.0, 1.0);
+    /// Images that fail to load are reported and replaced by a magenta checker,
+    /// so the render can go on. Use load() to handle errors instead.
+    pub fn new_filtered(path: &str,
+                        color_space: ColorSpace,
+                        filter: FilterMode,
+                        wrap_u: WrapMode,
+                        wrap_v: WrapMode) -> ImageTexture {
+        ImageTexture::load(path, color_space, filter, wrap_u, wrap_v).unwrap_or_else(|err| {
+            eprintln!("Failed to load texture: {err}");
+            ImageTexture::new_missing()
+        })
+    }
+
+    /// Load an image through the shared texture cache, so every image is only decoded once.
+    /// 8 and 16-bit images are decoded from the given color space.
+    /// Float images (.hdr, .exr) are always linear and keep values above 1, e.g. for emission.
+    pub fn load(path: &str,
+                color_space: ColorSpace,
+                filter: FilterMode,
+                wrap_u: WrapMode,
+                wrap_v: WrapMode) -> std::io::Result<ImageTexture> {
+        let mipmap = TextureCache::global().load(path, color_space)?;
+
+        Ok(ImageTexture { mipmap: mipmap.with_sampling(filter, wrap_u, wrap_v) })
+    }
+
+    /// The placeholder for missing images, magenta and black checkers that are hard to miss
+    pub fn new_missing() -> ImageTexture {
+        const CHECKS: usize = 8;
+        let texels = (0..CHECKS * CHECKS)
+            .map(|index| if (index / CHECKS + index % CHECKS).is_multiple_of(2) { [1.0, 0.0, 1.0] } else { [0.0, 0.0, 0.0] })
+            .collect();
+
+        ImageTexture {
+            mipmap: MipMap::new(CHECKS, CHECKS, texels, FilterMode::Nearest, WrapMode::Repeat, WrapMode::Repeat)
         }
+    }
+}
 
-        let u_clamped = Interval::new(0.0, 1.0).clamp(u);
-        let v_clamped = 1.0 - Interval::new(0.0, 1.0).clamp(v);
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _: &Point3, _: &Vec3) -> Color {
+        self.mipmap.lookup(u, v, 0.0)
+    }
 
-        let i = u_clamped * (self.image.width() - 1) as f64;
-        let j = v_clamped * (self.image.height() - 1) as f64;
-        // println!("{} {}", i, j);
-        let pixel = self.image.get_pixel(i as u32, j as u32);
-        let color_scale = 1.0 / 255.0;
+    fn value_footprint(&self, u: f64, v: f64, _: &Point3, _: &Vec3, footprint: &Footprint) -> Color {
+        let width = footprint.texel_width(self.mipmap.width(), self.mipmap.height());
 
-        Color::new(
-            color_scale * pixel[0] as f64,
-            color_scale * pixel[1] as f64,
-            color_scale * pixel[2] as f64,
-        )
+        self.mipmap.lookup(u, v, width)
     }
 }
 
@@ -136,10 +394,15 @@ impl NoiseTexture {
     pub fn new(scale: f64) -> Self {
         NoiseTexture { noise: Perlin::default(), scale }
     }
+
+    /// Marble with a different pattern per seed
+    pub fn new_seeded(scale: f64, seed: u64) -> Self {
+        NoiseTexture { noise: Perlin::new(256, seed), scale }
+    }
 }
 
 impl Texture for NoiseTexture {
-    fn value(&self, _: f64, _: f64, point: &Point3) -> Color {
+    fn value(&self, _: f64, _: f64, point: &Point3, _: &Vec3) -> Color {
         Color::new(1.0, 1.0, 1.0) * 0.5 * (1.0 + (self.scale * point.z() + 10.0 * self.noise.turbulence(*point, 7)).sin())
     }
 }
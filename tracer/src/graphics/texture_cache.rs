@@ -0,0 +1,76 @@
+//! Shared cache of decoded images.
+//!
+//! Materials that use the same image share one copy of its mip levels, instead of
+//! every texture decoding the file again. Images are keyed by their path and the
+//! color space they are decoded from, the same file read as sRGB and as linear data
+//! are different images.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+use image::DynamicImage;
+
+use crate::rendering::color::ColorSpace;
+
+use super::mipmap::{MipMap, FilterMode, WrapMode};
+
+
+#[derive(Default)]
+pub struct TextureCache {
+    images: Mutex<HashMap<(PathBuf, ColorSpace), MipMap>>
+}
+
+impl TextureCache {
+    /// The cache shared by all image textures
+    pub fn global() -> &'static TextureCache {
+        static CACHE: OnceLock<TextureCache> = OnceLock::new();
+        CACHE.get_or_init(TextureCache::default)
+    }
+
+    /// Decoded image at a path, loaded on first use.
+    /// Failed loads aren't cached, so a fixed file is picked up by the next load.
+    pub fn load(&self, path: &str, color_space: ColorSpace) -> std::io::Result<MipMap> {
+        // Different spellings of the same file share an entry
+        let key = (fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path)), color_space);
+
+        let mut images = self.images.lock().unwrap();
+        if let Some(mipmap) = images.get(&key) {
+            return Ok(mipmap.clone());
+        }
+
+        let mipmap = decode(path, color_space)?;
+        images.insert(key, mipmap.clone());
+
+        Ok(mipmap)
+    }
+}
+
+fn decode(path: &str, color_space: ColorSpace) -> std::io::Result<MipMap> {
+    let image = image::open(path).map_err(|err| match err {
+        image::ImageError::IoError(err) => Error::new(err.kind(), format!("{path}: {err}")),
+        err => Error::new(ErrorKind::InvalidData, format!("{path}: {err}"))
+    })?;
+
+    let color_space = match image {
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => ColorSpace::Linear,
+        _ => color_space
+    };
+
+    let image = image.to_rgb32f();
+    let texels = image
+        .pixels()
+        .map(|pixel| pixel.0.map(|channel| color_space.to_linear(channel as f64) as f32))
+        .collect();
+
+    Ok(MipMap::new(
+        image.width() as usize,
+        image.height() as usize,
+        texels,
+        FilterMode::Bilinear,
+        WrapMode::Clamp,
+        WrapMode::Clamp
+    ))
+}
@@ -0,0 +1,507 @@
+//! Textual texture expressions.
+//!
+//! Builds texture graphs from text, so looks can be described in scene files
+//! instead of code. An expression is a number, a quoted string, a name, or a call:
+//!
+//!     mix(
+//!         image("./resources/textures/earthmap.jpg", srgb),
+//!         ramp(worley(4, euclidean, f1, 1), 0, rgb(0.2, 0.1, 0), 1, rgb(1, 1, 1)),
+//!         remap(fbm(2, 5, 2, 0.5), 0.4, 0.6, 0, 1)
+//!     )
+//!
+//! Numbers and vectors are turned into constant textures wherever a texture is expected.
+//!
+//! Textures:  rgb(r, g, b), checker(scale, even, odd), noise(scale[, seed]), image(path, srgb|linear),
+//!            uv_checker(columns, rows, even, odd), uv_grid(columns, rows, line_width, line, fill),
+//!            triplanar(texture, scale, sharpness),
+//!            fbm(scale, octaves, lacunarity, gain[, seed]), ridged(scale, octaves, lacunarity, gain, offset[, seed]),
+//!            worley(scale, euclidean|manhattan|chebyshev, f1|f2|f2_minus_f1, jitter[, seed]),
+//!            wood(light, dark, rings_per_unit, distortion[, seed]), marble(scale, turbulence, octaves[, seed]),
+//!            gradient(start, end), ramp(input, position, color, position, color, ...)
+//! Nodes:     multiply(a, b), add(a, b), mix(a, b, mask), invert(a),
+//!            remap(a, from_min, from_max, to_min, to_max), hsv(a, hue_shift, saturation, value),
+//!            uv_transform(a, scale_u, scale_v, offset_u, offset_v, rotation),
+//!            transform(a, offset, scale, rotation_y)
+//! Vectors:   vec(x, y, z), same as rgb
+
+use std::sync::Arc;
+
+use crate::math::vec3::{Vec3, Color};
+use crate::rendering::color::ColorSpace;
+
+use super::{
+    texture::{
+        TextureSync, SolidColorTexture, CheckerTexture, ImageTexture, NoiseTexture,
+        UvCheckerTexture, UvGridTexture, TriplanarTexture
+    },
+    procedural::{
+        FbmTexture, RidgedTexture, WorleyTexture, DistanceMetric, WorleyFeature,
+        WoodTexture, MarbleTexture, GradientTexture, ColorRamp
+    },
+    mipmap::{FilterMode, WrapMode},
+    texture_nodes::{Multiply, Add, Mix, Invert, Remap, HueSaturation, UvTransform, PointTransform}
+};
+
+
+/// Parse a texture expression into the texture graph it describes
+pub fn parse_texture(text: &str) -> Result<Arc<TextureSync>, String> {
+    let mut parser = Parser { tokens: tokenize(text)?, position: 0 };
+    let value = parser.expression()?;
+    if let Some(token) = parser.tokens.get(parser.position) {
+        return Err(format!("unexpected {} after the expression", token.describe()));
+    }
+
+    value.into_texture()
+}
+
+
+#[derive(Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Text(String),
+    Name(String),
+    Open,
+    Close,
+    Comma
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Number(number) => format!("number {number}"),
+            Token::Text(text) => format!("string \"{text}\""),
+            Token::Name(name) => format!("'{name}'"),
+            Token::Open => "'('".to_string(),
+            Token::Close => "')'".to_string(),
+            Token::Comma => "','".to_string()
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => { chars.next(); }
+            '(' => { chars.next(); tokens.push(Token::Open); }
+            ')' => { chars.next(); tokens.push(Token::Close); }
+            ',' => { chars.next(); tokens.push(Token::Comma); }
+            '"' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => text.push(c),
+                        None => return Err(format!("unterminated string \"{text}"))
+                    }
+                }
+                tokens.push(Token::Text(text));
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => {
+                let mut number = String::new();
+                while let Some(&c) = chars.peek() {
+                    // Exponents may carry their own sign
+                    let is_exponent_sign = (c == '-' || c == '+') && number.ends_with(['e', 'E']);
+                    if c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || is_exponent_sign || number.is_empty() {
+                        number.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                let value = number.parse::<f64>().map_err(|_| format!("invalid number '{number}'"))?;
+                tokens.push(Token::Number(value));
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') { break; }
+                    name.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Name(name));
+            }
+            c => return Err(format!("unexpected character '{c}'"))
+        }
+    }
+
+    Ok(tokens)
+}
+
+
+enum Value {
+    Number(f64),
+    Vector(Vec3),
+    Text(String),
+    Name(String),
+    Texture(Arc<TextureSync>)
+}
+
+impl Value {
+    fn describe(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "a number",
+            Value::Vector(_) => "a vector",
+            Value::Text(_) => "a string",
+            Value::Name(_) => "a name",
+            Value::Texture(_) => "a texture"
+        }
+    }
+
+    fn into_texture(self) -> Result<Arc<TextureSync>, String> {
+        match self {
+            Value::Texture(texture) => Ok(texture),
+            Value::Number(number) => Ok(Arc::new(SolidColorTexture::new(Color::new(number, number, number)))),
+            Value::Vector(color) => Ok(Arc::new(SolidColorTexture::new(color))),
+            other => Err(format!("expected a texture, found {}", other.describe()))
+        }
+    }
+}
+
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize
+}
+
+impl Parser {
+    fn next(&mut self) -> Result<Token, String> {
+        let token = self.tokens.get(self.position).cloned().ok_or("unexpected end of expression")?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn expression(&mut self) -> Result<Value, String> {
+        match self.next()? {
+            Token::Number(number) => Ok(Value::Number(number)),
+            Token::Text(text) => Ok(Value::Text(text)),
+            Token::Name(name) => {
+                if self.tokens.get(self.position) != Some(&Token::Open) {
+                    return Ok(Value::Name(name));
+                }
+                self.position += 1;
+
+                let mut arguments = Vec::new();
+                if self.tokens.get(self.position) == Some(&Token::Close) {
+                    self.position += 1;
+                } else {
+                    loop {
+                        arguments.push(self.expression()?);
+                        match self.next()? {
+                            Token::Comma => continue,
+                            Token::Close => break,
+                            token => return Err(format!("expected ',' or ')' in '{name}', found {}", token.describe()))
+                        }
+                    }
+                }
+
+                call(Arguments { function: &name, values: arguments })
+            }
+            token => Err(format!("expected an expression, found {}", token.describe()))
+        }
+    }
+}
+
+
+/// Arguments of a call, taken by position and checked for their kind
+struct Arguments<'a> {
+    function: &'a str,
+    values: Vec<Value>
+}
+
+impl Arguments<'_> {
+    fn expect_count(&self, count: usize) -> Result<(), String> {
+        if self.values.len() != count {
+            return Err(format!("'{}' takes {count} arguments, found {}", self.function, self.values.len()));
+        }
+        Ok(())
+    }
+
+    fn take(&mut self, index: usize) -> Result<Value, String> {
+        let value = self.values
+            .get_mut(index)
+            .ok_or(format!("'{}' is missing argument {}", self.function, index + 1))?;
+
+        Ok(std::mem::replace(value, Value::Number(0.0)))
+    }
+
+    fn number(&mut self, index: usize) -> Result<f64, String> {
+        match self.take(index)? {
+            Value::Number(number) => Ok(number),
+            other => Err(format!("argument {} of '{}' should be a number, found {}", index + 1, self.function, other.describe()))
+        }
+    }
+
+    fn vector(&mut self, index: usize) -> Result<Vec3, String> {
+        match self.take(index)? {
+            Value::Vector(vector) => Ok(vector),
+            Value::Number(number) => Ok(Vec3::new(number, number, number)),
+            other => Err(format!("argument {} of '{}' should be a vector, found {}", index + 1, self.function, other.describe()))
+        }
+    }
+
+    fn text(&mut self, index: usize) -> Result<String, String> {
+        match self.take(index)? {
+            Value::Text(text) => Ok(text),
+            other => Err(format!("argument {} of '{}' should be a string, found {}", index + 1, self.function, other.describe()))
+        }
+    }
+
+    fn name(&mut self, index: usize) -> Result<String, String> {
+        match self.take(index)? {
+            Value::Name(name) => Ok(name),
+            other => Err(format!("argument {} of '{}' should be a name, found {}", index + 1, self.function, other.describe()))
+        }
+    }
+
+    fn texture(&mut self, index: usize) -> Result<Arc<TextureSync>, String> {
+        let function = self.function;
+        self.take(index)?
+            .into_texture()
+            .map_err(|error| format!("argument {} of '{function}': {error}", index + 1))
+    }
+}
+
+fn call(mut args: Arguments) -> Result<Value, String> {
+    let texture: Arc<TextureSync> = match args.function {
+        "rgb" | "vec" => {
+            args.expect_count(3)?;
+            return Ok(Value::Vector(Vec3::new(args.number(0)?, args.number(1)?, args.number(2)?)));
+        }
+        "checker" => {
+            args.expect_count(3)?;
+            Arc::new(CheckerTexture::new(args.number(0)?, args.texture(1)?, args.texture(2)?))
+        }
+        "uv_checker" => {
+            args.expect_count(4)?;
+            Arc::new(UvCheckerTexture::new(args.number(0)?, args.number(1)?, args.texture(2)?, args.texture(3)?))
+        }
+        "uv_grid" => {
+            args.expect_count(5)?;
+            Arc::new(UvGridTexture::new(
+                args.number(0)?, args.number(1)?, args.number(2)?, args.vector(3)?, args.vector(4)?
+            ))
+        }
+        "triplanar" => {
+            args.expect_count(3)?;
+            Arc::new(TriplanarTexture::new(args.texture(0)?, args.number(1)?, args.number(2)?))
+        }
+        "noise" => {
+            if args.values.len() != 2 {
+                args.expect_count(1)?;
+            }
+            let seed = if args.values.len() == 2 { args.number(1)? as u64 } else { 0 };
+            Arc::new(NoiseTexture::new_seeded(args.number(0)?, seed))
+        }
+        "image" => {
+            args.expect_count(2)?;
+            let path = args.text(0)?;
+            let color_space = match args.name(1)?.as_str() {
+                "srgb" => ColorSpace::Srgb,
+                "linear" => ColorSpace::Linear,
+                other => return Err(format!("unknown color space '{other}', expected srgb or linear"))
+            };
+            let texture = ImageTexture::load(&path, color_space, FilterMode::Bilinear, WrapMode::Clamp, WrapMode::Clamp)
+                .map_err(|err| format!("failed to load image: {err}"))?;
+            Arc::new(texture)
+        }
+        "fbm" => {
+            if args.values.len() != 5 {
+                args.expect_count(4)?;
+            }
+            let seed = if args.values.len() == 5 { args.number(4)? as u64 } else { 0 };
+            Arc::new(FbmTexture::new(args.number(0)?, args.number(1)? as usize, args.number(2)?, args.number(3)?, seed))
+        }
+        "ridged" => {
+            if args.values.len() != 6 {
+                args.expect_count(5)?;
+            }
+            let seed = if args.values.len() == 6 { args.number(5)? as u64 } else { 0 };
+            Arc::new(RidgedTexture::new(
+                args.number(0)?, args.number(1)? as usize, args.number(2)?, args.number(3)?, args.number(4)?, seed
+            ))
+        }
+        "worley" => {
+            if args.values.len() != 5 {
+                args.expect_count(4)?;
+            }
+            let metric = match args.name(1)?.as_str() {
+                "euclidean" => DistanceMetric::Euclidean,
+                "manhattan" => DistanceMetric::Manhattan,
+                "chebyshev" => DistanceMetric::Chebyshev,
+                other => return Err(format!("unknown distance metric '{other}'"))
+            };
+            let feature = match args.name(2)?.as_str() {
+                "f1" => WorleyFeature::F1,
+                "f2" => WorleyFeature::F2,
+                "f2_minus_f1" => WorleyFeature::F2MinusF1,
+                other => return Err(format!("unknown Worley feature '{other}'"))
+            };
+            let seed = if args.values.len() == 5 { args.number(4)? as u32 } else { 0 };
+            Arc::new(WorleyTexture::new(args.number(0)?, metric, feature, args.number(3)?, seed))
+        }
+        "wood" => {
+            if args.values.len() != 5 {
+                args.expect_count(4)?;
+            }
+            let seed = if args.values.len() == 5 { args.number(4)? as u64 } else { 0 };
+            Arc::new(WoodTexture::new(args.vector(0)?, args.vector(1)?, args.number(2)?, args.number(3)?, seed))
+        }
+        "marble" => {
+            if args.values.len() != 4 {
+                args.expect_count(3)?;
+            }
+            let seed = if args.values.len() == 4 { args.number(3)? as u64 } else { 0 };
+            Arc::new(MarbleTexture::new(args.number(0)?, args.number(1)?, args.number(2)? as usize, seed))
+        }
+        "gradient" => {
+            args.expect_count(2)?;
+            Arc::new(GradientTexture::new(args.vector(0)?, args.vector(1)?))
+        }
+        "ramp" => {
+            if args.values.len() < 3 || args.values.len().is_multiple_of(2) {
+                return Err("'ramp' takes an input followed by pairs of positions and colors".to_string());
+            }
+            let input = args.texture(0)?;
+            let mut stops = Vec::new();
+            for index in (1..args.values.len()).step_by(2) {
+                stops.push((args.number(index)?, args.vector(index + 1)?));
+            }
+            Arc::new(ColorRamp::new(input, &stops))
+        }
+        "multiply" => {
+            args.expect_count(2)?;
+            Arc::new(Multiply::new(args.texture(0)?, args.texture(1)?))
+        }
+        "add" => {
+            args.expect_count(2)?;
+            Arc::new(Add::new(args.texture(0)?, args.texture(1)?))
+        }
+        "mix" => {
+            args.expect_count(3)?;
+            Arc::new(Mix::new(args.texture(0)?, args.texture(1)?, args.texture(2)?))
+        }
+        "invert" => {
+            args.expect_count(1)?;
+            Arc::new(Invert::new(args.texture(0)?))
+        }
+        "remap" => {
+            args.expect_count(5)?;
+            Arc::new(Remap::new(
+                args.texture(0)?,
+                (args.number(1)?, args.number(2)?),
+                (args.number(3)?, args.number(4)?)
+            ))
+        }
+        "hsv" => {
+            args.expect_count(4)?;
+            Arc::new(HueSaturation::new(args.texture(0)?, args.number(1)?, args.number(2)?, args.number(3)?))
+        }
+        "uv_transform" => {
+            args.expect_count(6)?;
+            Arc::new(UvTransform::new(
+                args.texture(0)?,
+                (args.number(1)?, args.number(2)?),
+                (args.number(3)?, args.number(4)?),
+                args.number(5)?
+            ))
+        }
+        "transform" => {
+            args.expect_count(4)?;
+            Arc::new(PointTransform::new(args.texture(0)?, args.vector(1)?, args.vector(2)?, args.number(3)?))
+        }
+        other => return Err(format!("unknown function '{other}'"))
+    };
+
+    Ok(Value::Texture(texture))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color_of(text: &str) -> Color {
+        let texture = parse_texture(text).unwrap_or_else(|error| panic!("'{text}' failed to parse: {error}"));
+        texture.value(0.0, 0.0, &Vec3::zero(), &Vec3::new(0.0, 1.0, 0.0))
+    }
+
+    fn error_of(text: &str) -> String {
+        match parse_texture(text) {
+            Ok(_) => panic!("'{text}' should not parse"),
+            Err(error) => error
+        }
+    }
+
+    fn assert_color(color: Color, expected: (f64, f64, f64)) {
+        let matches = (color.x() - expected.0).abs() < 1e-9
+            && (color.y() - expected.1).abs() < 1e-9
+            && (color.z() - expected.2).abs() < 1e-9;
+        assert!(matches, "got ({}, {}, {}), expected {expected:?}", color.x(), color.y(), color.z());
+    }
+
+    #[test]
+    fn tokenizes_numbers_names_and_strings() {
+        let tokens = tokenize(r#"f(-1.5e-1, +2, "a b", x_1)"#).unwrap();
+        let expected = [
+            Token::Name("f".to_string()),
+            Token::Open,
+            Token::Number(-0.15),
+            Token::Comma,
+            Token::Number(2.0),
+            Token::Comma,
+            Token::Text("a b".to_string()),
+            Token::Comma,
+            Token::Name("x_1".to_string()),
+            Token::Close
+        ];
+        assert!(tokens == expected);
+    }
+
+    #[test]
+    fn unterminated_string_is_an_error() {
+        assert!(tokenize(r#"image("./earth.jpg, srgb)"#).is_err());
+        assert!(error_of(r#"image("./earth.jpg"#).contains("unterminated string"));
+    }
+
+    #[test]
+    fn numbers_and_vectors_become_constant_textures() {
+        assert_color(color_of("0.25"), (0.25, 0.25, 0.25));
+        assert_color(color_of("rgb(0.1, 0.2, 0.3)"), (0.1, 0.2, 0.3));
+        assert_color(color_of("vec(1, 2, 3)"), (1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn calls_nest() {
+        assert_color(color_of("multiply(rgb(1, 0.5, 0.25), 2)"), (2.0, 1.0, 0.5));
+        assert_color(color_of("add(invert(0.25), rgb(0, 0.1, 0.2))"), (0.75, 0.85, 0.95));
+        assert_color(color_of("mix(rgb(1, 0, 0), rgb(0, 0, 1), 0.25)"), (0.75, 0.0, 0.25));
+        assert_color(color_of("remap(0.5, 0, 1, 10, 20)"), (15.0, 15.0, 15.0));
+    }
+
+    #[test]
+    fn optional_seeds_are_accepted() {
+        assert!(parse_texture("noise(4)").is_ok());
+        assert!(parse_texture("noise(4, 7)").is_ok());
+        assert!(parse_texture("worley(4, euclidean, f1, 1, 3)").is_ok());
+        assert!(parse_texture("fbm(2, 5, 2, 0.5, 9)").is_ok());
+        assert!(parse_texture("ridged(2, 5, 2, 2, 1, 9)").is_ok());
+        assert!(parse_texture("wood(rgb(1, 1, 1), rgb(0, 0, 0), 6, 1.5, 9)").is_ok());
+        assert!(parse_texture("marble(4, 6, 5, 9)").is_ok());
+        assert!(error_of("marble(4, 6, 5, 9, 1)").contains("takes 3 arguments"));
+    }
+
+    #[test]
+    fn malformed_expressions_are_errors() {
+        assert!(error_of("").contains("unexpected end"));
+        assert!(error_of("rgb(1, 2)").contains("takes 3 arguments"));
+        assert!(error_of("sparkle(1)").contains("unknown function 'sparkle'"));
+        assert!(error_of("rgb(1, 2, 3) 4").contains("after the expression"));
+        assert!(error_of("multiply(1 2)").contains("expected ',' or ')'"));
+        assert!(error_of("checker(\"big\", 1, 0)").contains("should be a number"));
+        assert!(error_of("worley(4, hamming, f1, 1)").contains("unknown distance metric"));
+        assert!(error_of("1 $").contains("unexpected character '$'"));
+    }
+}
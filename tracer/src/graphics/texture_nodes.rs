@@ -0,0 +1,360 @@
+//! Texture nodes that combine and modify other textures.
+//!
+//! Every node is a Texture built from other textures, so looks can be assembled as
+//! graphs without writing a new texture each time, e.g. an image multiplied by
+//! ambient occlusion, mixed with dirt by a Worley mask. Footprints are passed on
+//! so image textures down the graph still filter themselves.
+
+use std::sync::Arc;
+
+use crate::math::vec3::{Point3, Vec3, Color};
+use crate::rendering::color::COLOR_WHITE;
+
+use super::texture::{Texture, TextureSync, Footprint};
+
+
+// Arithmetic
+// ==========
+
+pub struct Multiply {
+    first: Arc<TextureSync>,
+    second: Arc<TextureSync>
+}
+
+impl Multiply {
+    pub fn new(first: Arc<TextureSync>, second: Arc<TextureSync>) -> Self {
+        Multiply { first, second }
+    }
+}
+
+impl Texture for Multiply {
+    fn value(&self, u: f64, v: f64, point: &Point3, normal: &Vec3) -> Color {
+        self.first.value(u, v, point, normal) * self.second.value(u, v, point, normal)
+    }
+
+    fn value_footprint(&self, u: f64, v: f64, point: &Point3, normal: &Vec3, footprint: &Footprint) -> Color {
+        self.first.value_footprint(u, v, point, normal, footprint) * self.second.value_footprint(u, v, point, normal, footprint)
+    }
+}
+
+pub struct Add {
+    first: Arc<TextureSync>,
+    second: Arc<TextureSync>
+}
+
+impl Add {
+    pub fn new(first: Arc<TextureSync>, second: Arc<TextureSync>) -> Self {
+        Add { first, second }
+    }
+}
+
+impl Texture for Add {
+    fn value(&self, u: f64, v: f64, point: &Point3, normal: &Vec3) -> Color {
+        self.first.value(u, v, point, normal) + self.second.value(u, v, point, normal)
+    }
+
+    fn value_footprint(&self, u: f64, v: f64, point: &Point3, normal: &Vec3, footprint: &Footprint) -> Color {
+        self.first.value_footprint(u, v, point, normal, footprint) + self.second.value_footprint(u, v, point, normal, footprint)
+    }
+}
+
+/// The first texture where the mask is 0, the second one where it's 1
+pub struct Mix {
+    first: Arc<TextureSync>,
+    second: Arc<TextureSync>,
+    mask: Arc<TextureSync>
+}
+
+impl Mix {
+    pub fn new(first: Arc<TextureSync>, second: Arc<TextureSync>, mask: Arc<TextureSync>) -> Self {
+        Mix { first, second, mask }
+    }
+}
+
+impl Texture for Mix {
+    fn value(&self, u: f64, v: f64, point: &Point3, normal: &Vec3) -> Color {
+        let weight = self.mask.scalar_value(u, v, point, normal).clamp(0.0, 1.0);
+
+        (1.0 - weight) * self.first.value(u, v, point, normal) + weight * self.second.value(u, v, point, normal)
+    }
+
+    fn value_footprint(&self, u: f64, v: f64, point: &Point3, normal: &Vec3, footprint: &Footprint) -> Color {
+        let weight = self.mask.scalar_value_footprint(u, v, point, normal, footprint).clamp(0.0, 1.0);
+
+        (1.0 - weight) * self.first.value_footprint(u, v, point, normal, footprint)
+            + weight * self.second.value_footprint(u, v, point, normal, footprint)
+    }
+}
+
+
+// Adjustments
+// ===========
+
+/// One minus the input, per channel
+pub struct Invert {
+    input: Arc<TextureSync>
+}
+
+impl Invert {
+    pub fn new(input: Arc<TextureSync>) -> Self {
+        Invert { input }
+    }
+}
+
+impl Texture for Invert {
+    fn value(&self, u: f64, v: f64, point: &Point3, normal: &Vec3) -> Color {
+        COLOR_WHITE - self.input.value(u, v, point, normal)
+    }
+
+    fn value_footprint(&self, u: f64, v: f64, point: &Point3, normal: &Vec3, footprint: &Footprint) -> Color {
+        COLOR_WHITE - self.input.value_footprint(u, v, point, normal, footprint)
+    }
+}
+
+/// Linearly maps each channel from one range to another, without clamping
+pub struct Remap {
+    input: Arc<TextureSync>,
+    from: (f64, f64),
+    to: (f64, f64)
+}
+
+impl Remap {
+    pub fn new(input: Arc<TextureSync>, from: (f64, f64), to: (f64, f64)) -> Self {
+        Remap { input, from, to }
+    }
+
+    fn remap(&self, color: Color) -> Color {
+        let (from_min, from_max) = self.from;
+        let (to_min, to_max) = self.to;
+        let scale = if from_max != from_min { (to_max - to_min) / (from_max - from_min) } else { 0.0 };
+        let remap_channel = |value: f64| to_min + (value - from_min) * scale;
+
+        Color::new(remap_channel(color.x()), remap_channel(color.y()), remap_channel(color.z()))
+    }
+}
+
+impl Texture for Remap {
+    fn value(&self, u: f64, v: f64, point: &Point3, normal: &Vec3) -> Color {
+        self.remap(self.input.value(u, v, point, normal))
+    }
+
+    fn value_footprint(&self, u: f64, v: f64, point: &Point3, normal: &Vec3, footprint: &Footprint) -> Color {
+        self.remap(self.input.value_footprint(u, v, point, normal, footprint))
+    }
+}
+
+/// Rotates the hue and scales the saturation and value of the input
+pub struct HueSaturation {
+    input: Arc<TextureSync>,
+    hue_shift: f64,     // in turns, 0.5 gives the complementary color
+    saturation: f64,    // multiplier, 0 is grayscale
+    value: f64          // multiplier of the brightest channel
+}
+
+impl HueSaturation {
+    pub fn new(input: Arc<TextureSync>, hue_shift: f64, saturation: f64, value: f64) -> Self {
+        HueSaturation { input, hue_shift, saturation, value }
+    }
+
+    fn adjust(&self, color: Color) -> Color {
+        let (hue, saturation, value) = rgb_to_hsv(color);
+
+        hsv_to_rgb(
+            (hue + self.hue_shift).rem_euclid(1.0),
+            (saturation * self.saturation).clamp(0.0, 1.0),
+            value * self.value
+        )
+    }
+}
+
+impl Texture for HueSaturation {
+    fn value(&self, u: f64, v: f64, point: &Point3, normal: &Vec3) -> Color {
+        self.adjust(self.input.value(u, v, point, normal))
+    }
+
+    fn value_footprint(&self, u: f64, v: f64, point: &Point3, normal: &Vec3, footprint: &Footprint) -> Color {
+        self.adjust(self.input.value_footprint(u, v, point, normal, footprint))
+    }
+}
+
+/// Hue in turns, saturation and value in [0, 1]
+fn rgb_to_hsv(color: Color) -> (f64, f64, f64) {
+    let (r, g, b) = (color.x(), color.y(), color.z());
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let chroma = max - min;
+
+    let hue = if chroma <= 0.0 {
+        0.0
+    } else if max == r {
+        ((g - b) / chroma).rem_euclid(6.0) / 6.0
+    } else if max == g {
+        ((b - r) / chroma + 2.0) / 6.0
+    } else {
+        ((r - g) / chroma + 4.0) / 6.0
+    };
+    let saturation = if max > 0.0 { chroma / max } else { 0.0 };
+
+    (hue, saturation, max)
+}
+
+fn hsv_to_rgb(hue: f64, saturation: f64, value: f64) -> Color {
+    let channel = |n: f64| {
+        let k = (n + 6.0 * hue).rem_euclid(6.0);
+        value - value * saturation * k.min(4.0 - k).clamp(0.0, 1.0)
+    };
+
+    Color::new(channel(5.0), channel(3.0), channel(1.0))
+}
+
+
+// Coordinate transforms
+// =====================
+
+/// Samples the input at transformed texture coordinates.
+/// Coordinates are rotated around the center of the texture, scaled, then offset,
+/// so a scale of 2 tiles a repeating texture twice.
+pub struct UvTransform {
+    input: Arc<TextureSync>,
+    scale: (f64, f64),
+    offset: (f64, f64),
+    rotation: f64   // in degrees
+}
+
+impl UvTransform {
+    pub fn new(input: Arc<TextureSync>, scale: (f64, f64), offset: (f64, f64), rotation: f64) -> Self {
+        UvTransform { input, scale, offset, rotation }
+    }
+
+    /// The linear part of the transform, applied to a change in (u, v)
+    fn transform_delta(&self, du: f64, dv: f64) -> (f64, f64) {
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+
+        (self.scale.0 * (cos * du - sin * dv), self.scale.1 * (sin * du + cos * dv))
+    }
+
+    fn transform(&self, u: f64, v: f64) -> (f64, f64) {
+        let (u, v) = self.transform_delta(u - 0.5, v - 0.5);
+
+        (u + 0.5 + self.offset.0, v + 0.5 + self.offset.1)
+    }
+}
+
+impl Texture for UvTransform {
+    fn value(&self, u: f64, v: f64, point: &Point3, normal: &Vec3) -> Color {
+        let (u, v) = self.transform(u, v);
+        self.input.value(u, v, point, normal)
+    }
+
+    fn value_footprint(&self, u: f64, v: f64, point: &Point3, normal: &Vec3, footprint: &Footprint) -> Color {
+        let (u, v) = self.transform(u, v);
+        let (dudx, dvdx) = self.transform_delta(footprint.dudx, footprint.dvdx);
+        let (dudy, dvdy) = self.transform_delta(footprint.dudy, footprint.dvdy);
+        let footprint = Footprint { dudx, dvdx, dudy, dvdy, ..*footprint };
+
+        self.input.value_footprint(u, v, point, normal, &footprint)
+    }
+}
+
+/// Samples the input at a transformed point, for procedural textures.
+/// The pattern is scaled, rotated around the Y axis, then moved by the offset.
+pub struct PointTransform {
+    input: Arc<TextureSync>,
+    offset: Vec3,
+    scale: Vec3,
+    rotation_y: f64     // in degrees
+}
+
+impl PointTransform {
+    pub fn new(input: Arc<TextureSync>, offset: Vec3, scale: Vec3, rotation_y: f64) -> Self {
+        PointTransform { input, offset, scale, rotation_y }
+    }
+
+    /// Where a point of the transformed pattern comes from in the input
+    fn inverse_transform(&self, point: Vec3) -> Vec3 {
+        let unrotated = point.rotate_y(-self.rotation_y.to_radians());
+        let divide = |value: f64, scale: f64| if scale != 0.0 { value / scale } else { 0.0 };
+
+        Vec3::new(
+            divide(unrotated.x(), self.scale.x()),
+            divide(unrotated.y(), self.scale.y()),
+            divide(unrotated.z(), self.scale.z())
+        )
+    }
+}
+
+impl Texture for PointTransform {
+    fn value(&self, u: f64, v: f64, point: &Point3, normal: &Vec3) -> Color {
+        let normal = normal.rotate_y(-self.rotation_y.to_radians());
+        self.input.value(u, v, &self.inverse_transform(*point - self.offset), &normal)
+    }
+
+    fn value_footprint(&self, u: f64, v: f64, point: &Point3, normal: &Vec3, footprint: &Footprint) -> Color {
+        let footprint = Footprint {
+            dpdx: self.inverse_transform(footprint.dpdx),
+            dpdy: self.inverse_transform(footprint.dpdy),
+            ..*footprint
+        };
+        let normal = normal.rotate_y(-self.rotation_y.to_radians());
+
+        self.input.value_footprint(u, v, &self.inverse_transform(*point - self.offset), &normal, &footprint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::texture::SolidColorTexture;
+
+    #[test]
+    fn hsv_round_trips() {
+        let steps = [0.0, 0.1, 0.35, 0.5, 0.8, 1.0];
+        for r in steps {
+            for g in steps {
+                for b in steps {
+                    let (hue, saturation, value) = rgb_to_hsv(Color::new(r, g, b));
+                    assert!((0.0..1.0).contains(&hue) && (0.0..=1.0).contains(&saturation));
+
+                    let back = hsv_to_rgb(hue, saturation, value);
+                    let error = (back - Color::new(r, g, b)).length();
+                    assert!(error < 1e-9, "({r}, {g}, {b}) came back as ({}, {}, {})", back.x(), back.y(), back.z());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hsv_of_primaries() {
+        let hues = [(Color::new(1.0, 0.0, 0.0), 0.0), (Color::new(0.0, 1.0, 0.0), 1.0 / 3.0), (Color::new(0.0, 0.0, 1.0), 2.0 / 3.0)];
+        for (color, expected_hue) in hues {
+            let (hue, saturation, value) = rgb_to_hsv(color);
+            assert!((hue - expected_hue).abs() < 1e-12 && saturation == 1.0 && value == 1.0);
+        }
+    }
+
+    /// Black when looked up at a point, white when filtered over a footprint
+    struct Filtered;
+
+    impl Texture for Filtered {
+        fn value(&self, _: f64, _: f64, _: &Point3, _: &Vec3) -> Color {
+            Color::zero()
+        }
+
+        fn value_footprint(&self, _: f64, _: f64, _: &Point3, _: &Vec3, _: &Footprint) -> Color {
+            COLOR_WHITE
+        }
+    }
+
+    #[test]
+    fn mix_filters_its_mask_over_the_footprint() {
+        let mix = Mix::new(
+            Arc::new(SolidColorTexture::new(Color::zero())),
+            Arc::new(SolidColorTexture::new(COLOR_WHITE)),
+            Arc::new(Filtered)
+        );
+        let (point, normal) = (Point3::zero(), Vec3::new(0.0, 1.0, 0.0));
+
+        assert_eq!(mix.value(0.5, 0.5, &point, &normal).x(), 0.0);
+        assert_eq!(mix.value_footprint(0.5, 0.5, &point, &normal, &Footprint::default()).x(), 1.0);
+    }
+}
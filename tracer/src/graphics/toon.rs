@@ -0,0 +1,123 @@
+//! Non-photorealistic rendering: cel shading and outlines.
+//!
+//! The toon material quantizes the diffuse response to a key light into flat bands,
+//! and adds a hard rim light on the silhouette. It is shaded directly by the toon
+//! integrator (see Camera::integrator), which also draws outlines where the first hit
+//! changes abruptly between neighbouring rays. Seen by the path tracer, the material
+//! is plain diffuse.
+
+use std::sync::Arc;
+
+use crate::geometry::{Ray, hittable::HitRecord};
+use crate::math::{utils::get_random_point_on_unit_sphere, vec3::{Color, Point3, Vec3}};
+use crate::rendering::color::{COLOR_BLACK, COLOR_WHITE};
+
+use super::{material::Material, texture::{TextureSync, SolidColorTexture}};
+
+/// Darkest band, so the unlit side is not pitch black
+const AMBIENT: f64 = 0.2;
+
+
+// Toon material
+// =============
+pub struct Toon {
+    albedo: Arc<TextureSync>,
+    bands: u32,
+    light_direction: Vec3,  // towards the key light
+    rim_color: Color,
+    rim_width: f64          // in [0, 1], how far the rim reaches in from the silhouette
+}
+
+impl Toon {
+    pub fn new(color: Color, bands: u32, light_direction: Vec3) -> Self {
+        Toon::new_texture(Arc::new(SolidColorTexture::new(color)), bands, light_direction, COLOR_WHITE, 0.2)
+    }
+
+    pub fn new_texture(albedo: Arc<TextureSync>,
+                       bands: u32,
+                       light_direction: Vec3,
+                       rim_color: Color,
+                       rim_width: f64) -> Self {
+        Toon {
+            albedo,
+            bands: bands.max(1),
+            light_direction: light_direction.unit(),
+            rim_color,
+            rim_width
+        }
+    }
+}
+
+impl Material for Toon {
+    fn scatter(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<(Color, Ray)> {
+        let mut scatter_direction = hitrec.normal + get_random_point_on_unit_sphere();
+        if scatter_direction.near_zero() { scatter_direction = hitrec.normal; }
+
+        Some((
+            self.albedo.value(hitrec.u, hitrec.v, &hitrec.point, &hitrec.geometric_normal),
+            incident_ray.spawn(hitrec.point, scatter_direction)
+        ))
+    }
+
+    fn toon_shade(&self, incident_ray: &Ray, hitrec: &HitRecord) -> Option<Color> {
+        let lambert = hitrec.normal.dot(self.light_direction).max(0.0);
+        let band = (lambert * self.bands as f64).ceil() / self.bands as f64;
+        let diffuse = self.albedo.value(hitrec.u, hitrec.v, &hitrec.point, &hitrec.geometric_normal) * (AMBIENT + (1.0 - AMBIENT) * band);
+
+        // Rim on the lit side only, it reads as back light
+        let facing = hitrec.normal.dot(-incident_ray.direction.unit());
+        let rim = if lambert > 0.0 && facing < self.rim_width { self.rim_color } else { COLOR_BLACK };
+
+        Some(diffuse + rim)
+    }
+}
+
+
+// Outlines
+// ========
+/// Settings for the outlines drawn by the toon integrator.
+///
+/// Every camera sample is compared with samples offset by the outline width
+/// to each side. An outline is drawn where one of them hits a different object,
+/// a surface off the sample's tangent plane (depth), or a surface at a sharp
+/// angle to it (crease), or where only one of them hits anything (silhouette).
+/// Objects are told apart by their object ID, see HitRecord::object_id.
+pub struct Outline {
+    pub color: Color,
+    pub width: f64,             // in pixels
+    pub depth_threshold: f64,   // distance off the tangent plane, relative to the distance from the camera
+    pub crease_angle: f64       // in degrees, between the normals on both sides of an edge
+}
+
+impl Default for Outline {
+    fn default() -> Self {
+        Outline {
+            color: COLOR_BLACK,
+            width: 1.0,
+            depth_threshold: 0.05,
+            crease_angle: 30.0
+        }
+    }
+}
+
+impl Outline {
+    pub fn is_edge(&self, origin: Point3, center: &Option<HitRecord>, neighbour: &Option<HitRecord>) -> bool {
+        match (center, neighbour) {
+            (None, None) => false,
+            (Some(_), None) | (None, Some(_)) => true,
+            (Some(center), Some(neighbour)) => {
+                if center.object_id != neighbour.object_id {
+                    return true;
+                }
+
+                let distance = (center.point - origin).length();
+                let off_plane = (neighbour.point - center.point).dot(center.geometric_normal).abs();
+                if off_plane > self.depth_threshold * distance {
+                    return true;
+                }
+
+                center.geometric_normal.dot(neighbour.geometric_normal) < self.crease_angle.to_radians().cos()
+            }
+        }
+    }
+}
@@ -7,8 +7,9 @@ mod scenes;
 use std::env;
 use std::sync::Arc;
 
-use graphics::Camera;
-use rendering::render::render_scene;
+use graphics::{Camera, light_bvh::LightBVH};
+use geometry::Tessellation;
+use rendering::{render::render_scene, bake::{bake_texture, BakePass, BakeSettings}};
 // use buffer::write_img_ppm;
 
 use math::vec3::{Vec3, Point3, Color};
@@ -20,7 +21,10 @@ fn main() -> std::io::Result<()>{
         scenes::test_scene();
         return Ok(());
     }
-    if args.len() != 7 {
+    if args[1] == "bake" {
+        return bake(&args[2..]);
+    }
+    if args.len() != 7 && args.len() != 8 {
         panic!("Invalid arguments");
     }
 
@@ -60,8 +64,15 @@ fn main() -> std::io::Result<()>{
     // let world = rtweekend::cornell_box(&mut cam);
     // let world = scenes::cornell_box_dark_sphereflake(&mut cam);
     // let world = scenes::cornell_box_with_smokey_boxes(&mut cam);
-    let world = scenes::book2_final_scene(&mut cam);
     // let world = rtweekend::lit_world(&mut cam);
+    // An eighth argument renders the displacement scene, tessellated as given
+    let world = match args.get(7) {
+        Some(tessellation) => scenes::displaced_terrain(&mut cam, parse_tessellation(tessellation)),
+        None => scenes::book2_final_scene(&mut cam)
+    };
+
+    // Emissive objects are sampled directly
+    cam.light_tree = LightBVH::new(world.emitters());
 
     // Must be called!
     cam.initialize();
@@ -80,3 +91,47 @@ fn main() -> std::io::Result<()>{
 
     Ok(())
 }
+
+/// Tessellation by subdivisions, "level:64", or by edge length, "edge:0.05"
+fn parse_tessellation(arg: &str) -> Tessellation {
+    match arg.split_once(':') {
+        Some(("level", level)) => Tessellation::Level(level.parse::<usize>().unwrap()),
+        Some(("edge", length)) => Tessellation::EdgeLength(length.parse::<f64>().unwrap()),
+        _ => panic!("Unknown tessellation: {arg}")
+    }
+}
+
+/// Bake the lighting of a scene's surface into a texture.
+/// Arguments: pass (ao, irradiance or combined), resolution, samples per texel, trace depth, core count
+fn bake(args: &[String]) -> std::io::Result<()> {
+    if args.len() != 5 {
+        panic!("Invalid arguments");
+    }
+
+    let pass = match args[0].as_str() {
+        "ao" => BakePass::AmbientOcclusion { distance: 1.0 },
+        "irradiance" => BakePass::Irradiance,
+        "combined" => BakePass::Combined,
+        other => panic!("Unknown bake pass: {other}")
+    };
+    let resolution = args[1].parse::<usize>().unwrap();
+    let samples_per_texel: usize = args[2].parse::<usize>().unwrap();
+    let trace_depth: usize = args[3].parse::<usize>().unwrap();
+    let core_count: usize = args[4].parse::<usize>().unwrap();
+
+    let mut cam = Camera::default();
+    let (world, surface) = scenes::lightmap_floor(&mut cam);
+    cam.light_tree = LightBVH::new(world.emitters());
+
+    let texture_canvas = bake_texture(
+        core_count,
+        surface,
+        world,
+        Arc::new(cam),
+        BakeSettings { pass, resolution: (resolution, resolution), samples_per_texel, trace_depth }
+    );
+
+    texture_canvas.save_png("bake.png");
+
+    Ok(())
+}
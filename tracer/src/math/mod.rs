@@ -3,3 +3,4 @@ pub mod vec3;
 pub mod consts;
 pub mod interval;
 pub mod utils;
+pub mod spectrum;
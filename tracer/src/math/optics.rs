@@ -13,3 +13,29 @@ pub fn refract(incident: Vec3, normal: Vec3, etai_over_etat: f64) -> Vec3 {
 
     r_out_perp + r_out_parallel
 }
+
+/// Schlick's approximation of the Fresnel reflectance of a dielectric interface
+pub fn reflectance(cosine: f64, ref_idx: f64) -> f64 {
+    let r0 = ((1.0 -  ref_idx) / (1.0 + ref_idx)).powi(2);
+    
+    r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+}
+
+/// Fresnel amplitude reflection coefficients (s, p) of an interface between two dielectrics.
+/// n_i, cos_i: index of refraction and angle cosine on the incident side
+/// n_t, cos_t: index of refraction and angle cosine on the transmitted side
+pub fn fresnel_amplitudes(n_i: f64, cos_i: f64, n_t: f64, cos_t: f64) -> (f64, f64) {
+    let r_s = (n_i * cos_i - n_t * cos_t) / (n_i * cos_i + n_t * cos_t);
+    let r_p = (n_t * cos_i - n_i * cos_t) / (n_t * cos_i + n_i * cos_t);
+
+    (r_s, r_p)
+}
+
+/// Reflectance of a thin film, summing up all the rays bouncing inside it (Airy summation).
+/// r01, r12: amplitude reflection coefficients at the top and bottom of the film
+/// phase: phase difference between two consecutive rays leaving the film
+pub fn airy_reflectance(r01: f64, r12: f64, phase: f64) -> f64 {
+    let interference = 2.0 * r01 * r12 * phase.cos();
+
+    (r01 * r01 + r12 * r12 + interference) / (1.0 + r01 * r01 * r12 * r12 + interference)
+}
@@ -0,0 +1,74 @@
+//! Spectral helpers for setting up colors physically.
+//!
+//! Colors are linear sRGB (Rec. 709 primaries). Spectra are projected onto them through
+//! the CIE 1931 color matching functions, using the analytic multi-lobe fit from
+//! "Simple Analytic Approximations to the CIE XYZ Color Matching Functions"
+//! (Wyman, Sloan & Shirley 2013).
+
+use crate::math::vec3::Color;
+
+const PLANCK: f64 = 6.62607015e-34;     // J*s
+const LIGHT_SPEED: f64 = 2.99792458e8;  // m/s
+const BOLTZMANN: f64 = 1.380649e-23;    // J/K
+
+const WAVELENGTH_MIN: f64 = 380.0;  // nm
+const WAVELENGTH_MAX: f64 = 780.0;  // nm
+const WAVELENGTH_STEP: f64 = 5.0;   // nm
+
+/// Lumens per watt at 555nm, converts radiometric to photometric units
+pub const LUMINOUS_EFFICACY: f64 = 683.0;
+
+
+/// Relative luminance (Y) of a linear sRGB color
+pub fn luminance(color: Color) -> f64 {
+    0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z()
+}
+
+/// Color of a black body radiator at the given temperature, normalized to luminance 1.
+/// Around 1900K is candle light, 3200K tungsten, 5500K noon daylight and 6500K overcast sky.
+pub fn blackbody(kelvin: f64) -> Color {
+    let mut xyz = Color::zero();
+
+    let mut wavelength = WAVELENGTH_MIN;
+    while wavelength <= WAVELENGTH_MAX {
+        xyz += planck(wavelength, kelvin) * cie_xyz(wavelength);
+        wavelength += WAVELENGTH_STEP;
+    }
+
+    let rgb = xyz_to_linear_srgb(xyz / xyz.y());
+    // Very warm temperatures are slightly out of gamut
+    let rgb = Color::new(rgb.x().max(0.0), rgb.y().max(0.0), rgb.z().max(0.0));
+
+    rgb / luminance(rgb)
+}
+
+/// Spectral radiance of a black body by Planck's law, wavelength in nm
+fn planck(wavelength: f64, kelvin: f64) -> f64 {
+    let lambda = wavelength * 1e-9;
+    let numerator = 2.0 * PLANCK * LIGHT_SPEED * LIGHT_SPEED / lambda.powi(5);
+
+    numerator / ((PLANCK * LIGHT_SPEED / (lambda * BOLTZMANN * kelvin)).exp() - 1.0)
+}
+
+/// CIE 1931 2° standard observer color matching functions, wavelength in nm
+fn cie_xyz(wavelength: f64) -> Color {
+    // Gaussian with a different width on each side of its peak
+    let lobe = |peak: f64, width_below: f64, width_above: f64| {
+        let width = if wavelength < peak { width_below } else { width_above };
+        (-0.5 * ((wavelength - peak) / width).powi(2)).exp()
+    };
+
+    Color::new(
+        1.056 * lobe(599.8, 37.9, 31.0) + 0.362 * lobe(442.0, 16.0, 26.7) - 0.065 * lobe(501.1, 20.4, 26.2),
+        0.821 * lobe(568.8, 46.9, 40.5) + 0.286 * lobe(530.9, 16.3, 31.1),
+        1.217 * lobe(437.0, 11.8, 36.0) + 0.681 * lobe(459.0, 26.0, 13.8)
+    )
+}
+
+fn xyz_to_linear_srgb(xyz: Color) -> Color {
+    Color::new(
+        3.2406 * xyz.x() - 1.5372 * xyz.y() - 0.4986 * xyz.z(),
+        -0.9689 * xyz.x() + 1.8758 * xyz.y() + 0.0415 * xyz.z(),
+        0.0557 * xyz.x() - 0.2040 * xyz.y() + 1.0570 * xyz.z()
+    )
+}
@@ -31,3 +31,15 @@ pub fn random_f64_in_range(min: f64, max: f64) -> f64 {
     min + random::<f64>() * (max - min)
 }
 
+/// Build two unit vectors that form an orthonormal basis together with the given unit normal.
+/// "Building an Orthonormal Basis, Revisited" (Duff et al. 2017)
+pub fn orthonormal_basis(normal: Vec3) -> (Vec3, Vec3) {
+    let sign = 1.0_f64.copysign(normal.z());
+    let a = -1.0 / (sign + normal.z());
+    let b = normal.x() * normal.y() * a;
+
+    (
+        Vec3::new(1.0 + sign * normal.x() * normal.x() * a, sign * b, -sign * normal.x()),
+        Vec3::new(b, sign + normal.y() * normal.y() * a, -normal.y())
+    )
+}
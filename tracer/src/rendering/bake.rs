@@ -0,0 +1,119 @@
+//! Baking lighting into textures.
+//!
+//! Walks a surface's texture space, finds the surface point under every texel and
+//! path traces the light there, so the result can be saved and used as an image
+//! texture, e.g. as a lightmap by a real-time renderer.
+//! Texels no part of the surface maps to are left transparent.
+
+use std::thread;
+use std::sync::Arc;
+
+use indicatif::{ProgressBar, ProgressStyle, MultiProgress};
+use rand::random;
+
+use crate::graphics::Camera;
+use crate::geometry::hittable::{Hittable, HittableSync, UvSurfaceSync};
+use crate::rendering::{buffer::{Canvas, SliceBuffer}, color::{rasterize_color, COLOR_BLACK}, render::core_to_slices};
+
+
+/// What light a bake records
+pub enum BakePass {
+    /// The fraction of the hemisphere above the surface not blocked within the distance,
+    /// cosine weighted
+    AmbientOcclusion { distance: f64 },
+    /// Light arriving at the surface, as reflected by a white diffuse surface (irradiance / π).
+    /// Multiply by the albedo to light a diffuse surface.
+    Irradiance,
+    /// Everything the surface sends back along its normal, reflection and emission,
+    /// with its own material
+    Combined
+}
+
+pub struct BakeSettings {
+    pub pass: BakePass,
+    pub resolution: (usize, usize),     // width and height of the texture
+    pub samples_per_texel: usize,
+    pub trace_depth: usize
+}
+
+/// Bake a pass over a surface's texture space into a texture.
+///
+/// The camera only provides the background and the lights, it doesn't need to face the surface.
+/// The texture is sRGB encoded, like rendered frames.
+pub fn bake_texture(core_count: usize,
+                    surface: Arc<UvSurfaceSync>,
+                    object: Arc<HittableSync>,
+                    cam: Arc<Camera>,
+                    settings: BakeSettings) -> Canvas {
+    let (rows, columns) = core_to_slices(core_count);
+    let (width, height) = settings.resolution;
+
+    let mut texture_canvas = Canvas::new(width, height);
+    let multi_bar = MultiProgress::new();
+
+    thread::scope(|scope| {
+        let mut handles = Vec::new();
+        for row in 0..rows {
+            for col in 0..columns {
+                let (top, bottom) = (row * height / rows, (row + 1) * height / rows);
+                let (left, right) = (col * width / columns, (col + 1) * width / columns);
+                let slice = SliceBuffer::new_slice(right - left, bottom - top, top, left);
+                let (surface, object, cam, settings, multi_bar) = (&surface, &object, &cam, &settings, &multi_bar);
+
+                handles.push(scope.spawn(move || {
+                    bake_slice(slice, surface, object.clone(), cam, settings, multi_bar)
+                }));
+            }
+        }
+
+        for handle in handles {
+            texture_canvas.write_slice(&handle.join().unwrap());
+        }
+    });
+
+    texture_canvas
+}
+
+/// Bake the texels of a single slice
+fn bake_slice(mut slice: SliceBuffer,
+              surface: &Arc<UvSurfaceSync>,
+              object: Arc<dyn Hittable>,
+              cam: &Camera,
+              settings: &BakeSettings,
+              multi_bar: &MultiProgress) -> SliceBuffer {
+    let (width, height) = settings.resolution;
+
+    let height_bar: ProgressBar = multi_bar.add(ProgressBar::new(slice.height as u64));
+    height_bar.set_style(ProgressStyle::with_template(
+    "[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} ({eta})"
+    )
+    .unwrap()
+    .progress_chars("##-"));
+
+    for i in 0..slice.height {
+        let texel_row = (slice.abs_row_delta + i) as f64;
+
+        for j in 0..slice.width {
+            let texel_col = (slice.abs_col_delta + j) as f64;
+            let mut texel_color = COLOR_BLACK;
+            let mut texel_alpha = 0.0;
+
+            // Samples are spread over the texel, image rows go down while v goes up
+            for _ in 0..settings.samples_per_texel {
+                let u = (texel_col + random::<f64>()) / width as f64;
+                let v = 1.0 - (texel_row + random::<f64>()) / height as f64;
+
+                if let Some(hitrec) = surface.surface_at(u, v) {
+                    texel_color += cam.bake_sample(&hitrec, &settings.pass, &object, settings.trace_depth);
+                    texel_alpha += 1.0;
+                }
+            }
+            let texel = slice.pixels.get_pixel_mut(j as u32, i as u32);
+            *texel = rasterize_color(texel_color, texel_alpha, settings.samples_per_texel);
+        }
+        height_bar.inc(1);
+    }
+    height_bar.finish();
+
+    slice
+}
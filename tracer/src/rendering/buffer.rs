@@ -9,7 +9,7 @@ pub struct SliceBuffer {
     pub height: usize,
     pub abs_row_delta: usize,
     pub abs_col_delta: usize,
-    pub pixels: image::ImageBuffer<image::Rgb<u8>, Vec<u8>>
+    pub pixels: image::ImageBuffer<image::Rgba<u8>, Vec<u8>>
 }
 
 impl SliceBuffer {
@@ -10,24 +10,67 @@ pub const COLOR_SKY_BLUE: Color = Color::new_const(0.7, 0.8, 1.0);
 pub const COLOR_BLACK: Color = Color::new_const(0.0, 0.0, 0.0);
 
 
-/// Convert the accumulated raw Color value to a gamma corrected RGB value
-pub fn rasterize_color(pixel_color: Color, samples_per_pixel: usize) -> image::Rgb<u8> {
+/// How the values stored in an image relate to linear light.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    /// Gamma encoded colors, what 8-bit photos and painted albedo maps are stored in
+    Srgb,
+    /// Values used as they are, for normal maps, masks, heights and float images
+    Linear
+}
+
+impl ColorSpace {
+    /// Convert a stored value in [0, 1] to linear light
+    pub fn to_linear(self, value: f64) -> f64 {
+        match self {
+            ColorSpace::Srgb => srgb_to_linear(value),
+            ColorSpace::Linear => value
+        }
+    }
+}
+
+/// The sRGB transfer function, from encoded values to linear light
+pub fn srgb_to_linear(value: f64) -> f64 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// The inverse sRGB transfer function, from linear light to encoded values
+pub fn linear_to_srgb(value: f64) -> f64 {
+    if value <= 0.0031308 {
+        12.92 * value.max(0.0)
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+
+/// Convert the accumulated raw Color and alpha values to an sRGB encoded RGBA value
+/// 
+/// The accumulated color is premultiplied by alpha, the output is straight alpha.
+pub fn rasterize_color(pixel_color: Color, pixel_alpha: f64, samples_per_pixel: usize) -> image::Rgba<u8> {
     let mut r = pixel_color.x();
     let mut g = pixel_color.y();
     let mut b = pixel_color.z();
 
-    // Divide the color by the number of samples
-    let scale = 1.0 / (samples_per_pixel as f64);
-    r = (r * scale).sqrt();
-    g = (g * scale).sqrt();
-    b = (b * scale).sqrt();
+    // Divide the color by the accumulated alpha, which is the number of samples
+    // for fully opaque pixels
+    let scale = if pixel_alpha > 0.0 { 1.0 / pixel_alpha } else { 0.0 };
+    r = linear_to_srgb(r * scale);
+    g = linear_to_srgb(g * scale);
+    b = linear_to_srgb(b * scale);
+    let alpha = pixel_alpha / samples_per_pixel as f64;
     
     let intensity = Interval::new(0.0, 0.999);
     let r: u8 = (256.0 * intensity.clamp(r)) as u8;
     let g: u8 = (256.0 * intensity.clamp(g)) as u8;
     let b: u8 = (256.0 * intensity.clamp(b)) as u8;
+    let a: u8 = (256.0 * intensity.clamp(alpha)) as u8;
 
-    image::Rgb([r, g, b])
+    image::Rgba([r, g, b, a])
 }
 
 /// Return the color of the sky gradient when a ray hit it.
@@ -1,3 +1,4 @@
 pub mod buffer;
 pub mod render;
+pub mod bake;
 pub mod color;
@@ -14,7 +14,7 @@ use crate::rendering::{buffer::{Canvas, SliceBuffer}, color::rasterize_color};
 /// should the canvas be split into
 /// 
 /// Return (rows, columns)
-fn core_to_slices(core_count: usize) -> (usize, usize) {
+pub(crate) fn core_to_slices(core_count: usize) -> (usize, usize) {
     let cores: usize;
     if core_count == 0 { 
         if let Some(avail_cores) = thread::available_parallelism().ok() {
@@ -134,20 +134,22 @@ fn render_slice(slice_buffer: Arc<Mutex<SliceBuffer>>,
         // Render single line
         for j in 0..width {
             let mut pixel_color = Color::zero();
+            let mut pixel_alpha = 0.0;
             let pixel_col = (slice_data.abs_col_delta + j as usize) as f64;
 
             // Render single pixel
             for _ in 0..samples_per_pixel {
-                let color = cam.render_ray(
+                let (color, alpha) = cam.render_ray(
                     pixel_row,
                     pixel_col,
                     &object, 
                     trace_depth
                 );
                 pixel_color += color;
+                pixel_alpha += alpha;
             }
             let pixel = slice_data.pixels.get_pixel_mut(j as u32, i as u32);
-            *pixel = rasterize_color(pixel_color, samples_per_pixel);
+            *pixel = rasterize_color(pixel_color, pixel_alpha, samples_per_pixel);
         }
         // slice_vec.push(line_buffer);
         height_bar.inc(1);
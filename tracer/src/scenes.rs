@@ -1,13 +1,15 @@
 #![allow(dead_code)]
 /// This module contains mostly scene descriptions, and some util functions.
 
+use std::f64::consts::PI;
 use std::sync::Arc;
 
 use rand::random;
-use crate::geometry::ConstantMedium;
-use crate::graphics::Camera;
+use crate::geometry::{ConstantMedium, SubsurfaceMedium};
+use crate::graphics::{Camera, Integrator};
 use crate::math::{
     utils::random_f64_in_range,
+    spectrum::blackbody,
     vec3::{Color, Point3, Vec3}
 };
 use crate::geometry::{
@@ -16,20 +18,43 @@ use crate::geometry::{
     box_new, 
     RotateY, 
     Translate,
+    Triangle,
+    Vertex,
+    Displacement,
+    Tessellation,
     new_sphereflake_upright, 
-    hittable::{HittableSync, HittableComposite}, 
+    hittable::{HittableSync, HittableComposite, UvSurfaceSync}, 
 };
 
 use crate::graphics::{
     bvh::BVH,
-    light::DiffuseLight,
-    material::{Lambertian, Metal, Dielectric, MaterialSync},
-    texture::{SolidColorTexture, CheckerTexture, ImageTexture, NoiseTexture},
+    light::{DiffuseLight, PointLight, ProjectorLight},
+    ies::IesProfile,
+    material::{Lambertian, Metal, Dielectric, MaterialSync, MixMaterial},
+    normal_map::NormalMapped,
+    alpha_mask::AlphaMasked,
+    layered::{Clearcoat, ThinFilm, FilmSubstrate},
+    microfacet::AnisotropicMetal,
+    sheen::Sheen,
+    merl::{MerlMaterial, MerlSampling},
+    compositing::{Holdout, ShadowCatcher},
+    toon::{Toon, Outline},
+    texture::{
+        TextureSync, SolidColorTexture, CheckerTexture, ImageTexture, NoiseTexture,
+        UvCheckerTexture, UvGridTexture, TriplanarTexture
+    },
+    mipmap::{FilterMode, WrapMode},
+    procedural::{
+        FbmTexture, RidgedTexture, WorleyTexture, DistanceMetric, WorleyFeature,
+        WoodTexture, MarbleTexture, GradientTexture, ColorRamp
+    },
+    texture_expression::parse_texture,
+    noise::{NoiseSource, NoiseBasis},
 };
 
 use crate::rendering::{
     render::render_scene, 
-    color::{COLOR_SKY_BLUE, COLOR_BLACK, COLOR_WHITE}
+    color::{COLOR_SKY_BLUE, COLOR_BLACK, COLOR_WHITE, ColorSpace}
 };
 
 fn generate_default_plane(plane_size: f64, color: Option<Color>) -> Quad {
@@ -61,7 +86,7 @@ pub fn test_scene() {
     objects.push(Arc::new(Sphere::new(
         Point3::new(0.0, sphere_radius, 0.0),
         sphere_radius,
-        Arc::new(Dielectric{ir: 1.5})
+        Arc::new(Dielectric::new(1.5))
     )));
     
     objects.push(Arc::new(Sphere::new(
@@ -157,7 +182,7 @@ pub fn one_weekend_endgame(cam: &mut Camera, grid_size: i32) -> Arc<HittableSync
                     sphere_material = Arc::new(Metal{albedo, fuzz});
 
                 } else {
-                    sphere_material = Arc::new(Dielectric{ir: 1.5});
+                    sphere_material = Arc::new(Dielectric::new(1.5));
                 }
                 sphere = Sphere::new(center, 0.2, sphere_material);
                 objects.push(Arc::new(sphere));
@@ -168,7 +193,7 @@ pub fn one_weekend_endgame(cam: &mut Camera, grid_size: i32) -> Arc<HittableSync
     objects.push(Arc::new(Sphere::new(
         Point3::new(0.0, 1.0, 0.0),
         1.0,
-        Arc::new(Dielectric{ir: 1.5})
+        Arc::new(Dielectric::new(1.5))
     )));
     
     objects.push(Arc::new(Sphere::new(
@@ -223,7 +248,7 @@ pub fn cool_effects(sphere_count: u32, distance: f64) -> HittableComposite {
         objects.push(Arc::new(Sphere::new(
             Point3::new(x, y, z),
             0.2, 
-            Arc::new(Dielectric{ir: 1.5})
+            Arc::new(Dielectric::new(1.5))
         )));
     }
 
@@ -259,7 +284,7 @@ pub fn row_of_glass(sphere_count: u32, distance: f64) -> HittableComposite {
         objects.push(Arc::new(Sphere::new(
             Point3::new((0.1 + distance) * i as f64, 0.2, 0.0),
             0.2, 
-            Arc::new(Dielectric{ir: 1.5})
+            Arc::new(Dielectric::new(1.5))
         )));
     }
 
@@ -438,7 +463,7 @@ pub fn two_checkered_spheres(cam: &mut Camera) -> HittableComposite {
 pub fn earth(cam: &mut Camera) -> HittableComposite {
     let mut objects: Vec<Arc<HittableSync>> = Vec::new();
 
-    let earth_texture = ImageTexture::new("./resources/textures/earthmap.jpg");
+    let earth_texture = ImageTexture::new("./resources/textures/earthmap.jpg", ColorSpace::Srgb);
     let earth_surface = Lambertian::new_texture(Arc::new(earth_texture));
     let globe = Sphere::new(
         Point3::new(0.0, 2.0, 0.0),
@@ -923,7 +948,7 @@ pub fn book2_final_scene(cam: &mut Camera) -> Arc<HittableSync> {
     world.add_hittable(Arc::new(Sphere::new(
         Point3::new(260.0, 150.0, 45.0),
         50.0,
-        Arc::new(Dielectric{ir: 1.5})
+        Arc::new(Dielectric::new(1.5))
     )));
 
     world.add_hittable(Arc::new(Sphere::new(
@@ -935,7 +960,7 @@ pub fn book2_final_scene(cam: &mut Camera) -> Arc<HittableSync> {
     let mut boundry = Arc::new(Sphere::new(
         Point3::new(360.0, 150.0, 145.0),
         70.0,
-        Arc::new(Dielectric{ir: 1.5})
+        Arc::new(Dielectric::new(1.5))
     ));
 
     world.add_hittable(boundry.clone());
@@ -948,7 +973,7 @@ pub fn book2_final_scene(cam: &mut Camera) -> Arc<HittableSync> {
     boundry = Arc::new(Sphere::new(Point3::
         zero(), 
         5000.0, 
-        Arc::new(Dielectric{ir: 1.5})
+        Arc::new(Dielectric::new(1.5))
     ));
     world.add_hittable(Arc::new(ConstantMedium::new_color(
         boundry, 
@@ -957,7 +982,7 @@ pub fn book2_final_scene(cam: &mut Camera) -> Arc<HittableSync> {
     )));
 
     // earth
-    let earth_texture = ImageTexture::new("./resources/textures/earthmap.jpg");
+    let earth_texture = ImageTexture::new("./resources/textures/earthmap.jpg", ColorSpace::Srgb);
     let emat = Lambertian::new_texture(Arc::new(earth_texture));
     world.add_hittable(Arc::new(Sphere::new(
         Point3::new(400.0,200.0,400.0),
@@ -1022,4 +1047,1178 @@ pub fn sphereflake_on_sandy_plane(cam: &mut Camera) -> Arc<HittableSync> {
     cam.background = Color::new(0.5, 0.6, 1.0);
 
     Arc::new(BVH::new(&mut world))
-}
\ No newline at end of file
+}
+
+pub fn ice_in_water_glass(cam: &mut Camera) -> Arc<HittableSync> {
+    let mut world = HittableComposite::new();
+
+    world.add_hittable(Arc::new(generate_default_plane(
+        30.0,
+        Some(Color::new(0.5, 0.5, 0.5))
+    )));
+
+    let center = Point3::new(0.0, 1.0, 0.0);
+
+    // The water overlaps the glass everywhere but the shell, and wins by priority.
+    // The ice is inside the water, and wins over it.
+    world.add_hittable(Arc::new(Sphere::new(
+        center,
+        1.0,
+        Arc::new(Dielectric::new_with_priority(1.5, 1))
+    )));
+    world.add_hittable(Arc::new(Sphere::new(
+        center,
+        0.95,
+        Arc::new(Dielectric::new_with_priority(1.33, 2))
+    )));
+    world.add_hittable(Arc::new(Sphere::new(
+        center + Vec3::new(0.2, 0.5, 0.0),
+        0.3,
+        Arc::new(Dielectric::new_with_priority(1.31, 3))
+    )));
+
+    // Pebble at the bottom of the water
+    world.add_hittable(Arc::new(Sphere::new(
+        center + Vec3::new(-0.3, -0.7, 0.1),
+        0.2,
+        Arc::new(Lambertian::new(Color::new(0.8, 0.3, 0.1)))
+    )));
+
+    cam.look_from = Point3::new(0.0, 2.0, 5.0);
+    cam.look_at = center;
+    cam.vfov = 40.0;
+    cam.background = COLOR_SKY_BLUE;
+
+    Arc::new(BVH::new(&mut world))
+}
+
+pub fn bump_mapped_spheres(cam: &mut Camera) -> Arc<HittableSync> {
+    let mut world = HittableComposite::new();
+
+    world.add_hittable(Arc::new(generate_default_plane(
+        30.0,
+        Some(Color::new(0.5, 0.5, 0.5))
+    )));
+
+    // Procedural bumps on a diffuse sphere
+    let bumpy_clay = NormalMapped::new_bump_map(
+        Arc::new(Lambertian::new(Color::new(0.8, 0.5, 0.3))),
+        Arc::new(NoiseTexture::new(8.0)),
+        0.02
+    );
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(-1.1, 1.0, 0.0),
+        1.0,
+        Arc::new(bumpy_clay)
+    )));
+
+    // Image bumps on a metal sphere
+    let embossed_metal = NormalMapped::new_bump_map(
+        Arc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.1)),
+        Arc::new(ImageTexture::new("./resources/textures/earthmap.jpg", ColorSpace::Linear)),
+        0.05
+    );
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(1.1, 1.0, 0.0),
+        1.0,
+        Arc::new(embossed_metal)
+    )));
+
+    // Tiles tilted left and right by a checkered normal map
+    let tiles = NormalMapped::new_normal_map(
+        Arc::new(Lambertian::new(Color::new(0.2, 0.4, 0.8))),
+        Arc::new(CheckerTexture::new_color(
+            0.2,
+            Color::new(0.75, 0.5, 0.9),
+            Color::new(0.25, 0.5, 0.9)
+        )),
+        1.0
+    );
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(0.0, 0.5, 1.5),
+        0.5,
+        Arc::new(tiles)
+    )));
+
+    cam.look_from = Point3::new(0.0, 2.0, 6.0);
+    cam.look_at = Point3::new(0.0, 1.0, 0.0);
+    cam.vfov = 40.0;
+    cam.background = COLOR_SKY_BLUE;
+
+    Arc::new(BVH::new(&mut world))
+}
+
+pub fn alpha_cutout_fence(cam: &mut Camera) -> Arc<HittableSync> {
+    let mut world = HittableComposite::new();
+
+    world.add_hittable(Arc::new(generate_default_plane(
+        30.0,
+        Some(Color::new(0.7, 0.7, 0.7))
+    )));
+
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(0.0, 1.0, -1.5),
+        1.0,
+        Arc::new(Lambertian::new(Color::new(0.8, 0.2, 0.2)))
+    )));
+
+    // Lattice fence, the holes let both camera rays and shadow rays through
+    let lattice = AlphaMasked::new_cutout(
+        Arc::new(Lambertian::new(Color::new(0.6, 0.4, 0.2))),
+        Arc::new(CheckerTexture::new_color(0.25, COLOR_WHITE, COLOR_BLACK)),
+        0.5
+    );
+    world.add_hittable(Arc::new(RotateY::new(
+        Arc::new(Quad::new(
+            Point3::new(-2.0, 0.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.5, 0.0),
+            Arc::new(lattice)
+        )),
+        20.0
+    )));
+
+    // Gauze that blocks about a third of the light passing through it
+    let gauze = AlphaMasked::new_stochastic(
+        Arc::new(Lambertian::new(Color::new(0.9, 0.9, 0.9))),
+        Arc::new(SolidColorTexture::new(COLOR_WHITE * 0.3))
+    );
+    world.add_hittable(Arc::new(Translate::new(
+        Arc::new(Quad::new(
+            Point3::zero(),
+            Vec3::new(1.5, 0.0, 0.0),
+            Vec3::new(0.0, 1.5, 0.0),
+            Arc::new(gauze)
+        )),
+        Vec3::new(0.5, 0.0, 0.5)
+    )));
+
+    world.add_hittable(Arc::new(Quad::new(
+        Point3::new(-1.0, 5.0, 1.0),
+        Vec3::new(2.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 2.0),
+        Arc::new(DiffuseLight::new_color(COLOR_WHITE * 8.0))
+    )));
+
+    cam.look_from = Point3::new(0.0, 2.0, 6.0);
+    cam.look_at = Point3::new(0.0, 0.8, 0.0);
+    cam.vfov = 40.0;
+    cam.background = Color::new(0.1, 0.1, 0.15);
+
+    Arc::new(BVH::new(&mut world))
+}
+
+pub fn mixed_materials(cam: &mut Camera) -> Arc<HittableSync> {
+    let mut world = HittableComposite::new();
+
+    world.add_hittable(Arc::new(generate_default_plane(
+        30.0,
+        Some(Color::new(0.5, 0.5, 0.5))
+    )));
+
+    // Rust patches on polished steel
+    let rusty_steel = MixMaterial::new_select(
+        Arc::new(Metal::new(Color::new(0.8, 0.8, 0.85), 0.05)),
+        Arc::new(Lambertian::new(Color::new(0.45, 0.2, 0.08))),
+        Arc::new(NoiseTexture::new(3.0))
+    );
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(-1.1, 1.0, 0.0),
+        1.0,
+        Arc::new(rusty_steel)
+    )));
+
+    // Checkerboard of glass and diffuse, the diffuse squares softly glowing
+    let glowing_tiles = MixMaterial::new_blend(
+        Arc::new(Lambertian::new(Color::new(0.2, 0.6, 0.3))),
+        Arc::new(DiffuseLight::new_color(Color::new(1.0, 0.8, 0.4))),
+        Arc::new(SolidColorTexture::new(COLOR_WHITE * 0.3))
+    );
+    let glass_checkers = MixMaterial::new_select(
+        Arc::new(Dielectric::new(1.5)),
+        Arc::new(glowing_tiles),
+        Arc::new(CheckerTexture::new_color(0.3, COLOR_BLACK, COLOR_WHITE))
+    );
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(1.1, 1.0, 0.0),
+        1.0,
+        Arc::new(glass_checkers)
+    )));
+
+    cam.look_from = Point3::new(0.0, 2.0, 6.0);
+    cam.look_at = Point3::new(0.0, 1.0, 0.0);
+    cam.vfov = 40.0;
+    cam.background = COLOR_SKY_BLUE;
+
+    Arc::new(BVH::new(&mut world))
+}
+
+pub fn clearcoat_showcase(cam: &mut Camera) -> Arc<HittableSync> {
+    let mut world = HittableComposite::new();
+
+    world.add_hittable(Arc::new(generate_default_plane(
+        30.0,
+        Some(Color::new(0.5, 0.5, 0.5))
+    )));
+
+    // Car paint
+    let car_paint = Clearcoat::new_clear(
+        Arc::new(Lambertian::new(Color::new(0.6, 0.02, 0.02))),
+        1.5,
+        0.0
+    );
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(-2.2, 1.0, 0.0),
+        1.0,
+        Arc::new(car_paint)
+    )));
+
+    // Varnished wood, the varnish tints it amber
+    let varnished_wood = Clearcoat::new(
+        Arc::new(Lambertian::new_texture(Arc::new(NoiseTexture::new(2.0)))),
+        1.5,
+        0.05,
+        0.5,
+        Color::new(0.9, 0.6, 0.3)
+    );
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        Arc::new(varnished_wood)
+    )));
+
+    // Glazed ceramic over brushed metal
+    let glazed = Clearcoat::new(
+        Arc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.4)),
+        1.6,
+        0.0,
+        0.3,
+        Color::new(0.3, 0.6, 0.9)
+    );
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(2.2, 1.0, 0.0),
+        1.0,
+        Arc::new(glazed)
+    )));
+
+    cam.look_from = Point3::new(0.0, 2.0, 8.0);
+    cam.look_at = Point3::new(0.0, 1.0, 0.0);
+    cam.vfov = 40.0;
+    cam.background = COLOR_SKY_BLUE;
+
+    Arc::new(BVH::new(&mut world))
+}
+
+pub fn subsurface_marble(cam: &mut Camera) -> Arc<HittableSync> {
+    let mut world = HittableComposite::new();
+
+    let noise_texture = Arc::new(Lambertian::new_texture(Arc::new(
+        NoiseTexture::new(4.0)
+    )));
+    world.add_hittable(Arc::new(
+        Sphere::new(
+            Point3::new(0.0, -1000.0, 0.0),
+            1000.0,
+            noise_texture
+        )
+    ));
+
+    // Marble, barely absorbs and scatters a lot
+    let marble = Arc::new(Sphere::new(
+        Point3::new(0.0, 2.0, 0.0),
+        2.0,
+        Arc::new(Lambertian::new(COLOR_WHITE))
+    ));
+    world.add_hittable(Arc::new(SubsurfaceMedium::new_mean_free_path(
+        marble,
+        1.5,
+        Color::new(0.99, 0.98, 0.96),
+        Color::new(0.3, 0.3, 0.3)
+    )));
+
+    // Wax, light goes deep and comes out warm
+    let wax = Arc::new(Sphere::new(
+        Point3::new(0.0, 1.0, 3.5),
+        1.0,
+        Arc::new(Lambertian::new(COLOR_WHITE))
+    ));
+    world.add_hittable(Arc::new(SubsurfaceMedium::new_coefficients(
+        wax,
+        1.45,
+        Color::new(4.0, 3.0, 2.0),
+        Color::new(0.02, 0.1, 0.4)
+    )));
+
+    // Skin, red scatters much further than blue
+    let skin = Arc::new(Sphere::new(
+        Point3::new(0.0, 1.0, -3.5),
+        1.0,
+        Arc::new(Lambertian::new(COLOR_WHITE))
+    ));
+    world.add_hittable(Arc::new(SubsurfaceMedium::new_mean_free_path(
+        skin,
+        1.4,
+        Color::new(0.95, 0.7, 0.55),
+        Color::new(0.4, 0.12, 0.06)
+    )));
+
+    let light = DiffuseLight::new_color(Color::new(4.0, 4.0, 4.0));
+    world.add_hittable(Arc::new(Quad::new(
+        Point3::new(-2.0, 6.0, -2.0),
+        Vec3::new(4.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 4.0),
+        Arc::new(light)
+    )));
+
+    cam.look_from = Point3::new(13.0, 2.0, 3.0);
+    cam.look_at = Point3::new(0.0, 1.0, 0.0);
+    cam.defocus_angle = 0.0;
+    cam.background = Color::new(0.2, 0.25, 0.3);
+
+    Arc::new(BVH::new(&mut world))
+}
+
+pub fn thin_film_iridescence(cam: &mut Camera) -> Arc<HittableSync> {
+    let mut world = HittableComposite::new();
+
+    world.add_hittable(Arc::new(generate_default_plane(
+        30.0,
+        Some(Color::new(0.1, 0.1, 0.1))
+    )));
+
+    // Soap bubble, a swirly water film with air on both sides
+    let soap_bubble = ThinFilm::new_texture(
+        Arc::new(Dielectric::new(1.0)),
+        FilmSubstrate::Dielectric { ior: 1.0 },
+        1.33,
+        800.0,
+        Arc::new(NoiseTexture::new(1.5))
+    );
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(-2.2, 1.2, 0.0),
+        1.0,
+        Arc::new(soap_bubble)
+    )));
+
+    // Oil slick on a puddle over the dark ground
+    let oil_slick = ThinFilm::new_texture(
+        Arc::new(Dielectric::new(1.33)),
+        FilmSubstrate::Dielectric { ior: 1.33 },
+        1.47,
+        600.0,
+        Arc::new(NoiseTexture::new(0.8))
+    );
+    // Faces up, u x v points along +Y
+    world.add_hittable(Arc::new(Quad::new(
+        Point3::new(-1.0, 0.001, 1.0),
+        Vec3::new(2.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, -2.0),
+        Arc::new(oil_slick)
+    )));
+
+    // Anodized titanium
+    let anodized = ThinFilm::new(
+        Arc::new(Metal::new(Color::new(0.6, 0.6, 0.6), 0.05)),
+        FilmSubstrate::Conductor { reflectance: Color::new(0.55, 0.5, 0.45) },
+        2.2,
+        250.0
+    );
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(2.2, 1.0, 0.0),
+        1.0,
+        Arc::new(anodized)
+    )));
+
+    cam.look_from = Point3::new(0.0, 3.0, 8.0);
+    cam.look_at = Point3::new(0.0, 0.8, 0.0);
+    cam.vfov = 40.0;
+    cam.background = Color::new(0.8, 0.85, 0.9);
+
+    Arc::new(BVH::new(&mut world))
+}
+
+pub fn brushed_metals(cam: &mut Camera) -> Arc<HittableSync> {
+    let mut world = HittableComposite::new();
+
+    world.add_hittable(Arc::new(generate_default_plane(
+        30.0,
+        Some(Color::new(0.3, 0.3, 0.3))
+    )));
+
+    // Brushed around the vertical axis
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(-1.1, 1.0, 0.0),
+        1.0,
+        Arc::new(AnisotropicMetal::new(Color::new(0.9, 0.9, 0.9), 0.05, 0.4))
+    )));
+
+    // Same, brushed from pole to pole
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(1.1, 1.0, 0.0),
+        1.0,
+        Arc::new(AnisotropicMetal::new(Color::new(0.95, 0.7, 0.4), 0.4, 0.05))
+    )));
+
+    // Brushed steel plate, the brushing direction swirls with the noise
+    let swirled_steel = AnisotropicMetal::new_texture(
+        Arc::new(SolidColorTexture::new(Color::new(0.8, 0.8, 0.85))),
+        0.02,
+        0.3,
+        Arc::new(NoiseTexture::new(1.0))
+    );
+    world.add_hittable(Arc::new(Quad::new(
+        Point3::new(-1.5, 0.001, 1.2),
+        Vec3::new(3.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 1.5),
+        Arc::new(swirled_steel)
+    )));
+
+    world.add_hittable(Arc::new(Quad::new(
+        Point3::new(-3.0, 4.0, 2.0),
+        Vec3::new(6.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 0.5),
+        Arc::new(DiffuseLight::new_color(COLOR_WHITE * 10.0))
+    )));
+
+    cam.look_from = Point3::new(0.0, 2.5, 6.0);
+    cam.look_at = Point3::new(0.0, 0.8, 0.0);
+    cam.vfov = 40.0;
+    cam.background = Color::new(0.1, 0.1, 0.12);
+
+    Arc::new(BVH::new(&mut world))
+}
+
+pub fn cloth_and_velvet(cam: &mut Camera) -> Arc<HittableSync> {
+    let mut world = HittableComposite::new();
+
+    world.add_hittable(Arc::new(generate_default_plane(
+        30.0,
+        Some(Color::new(0.5, 0.5, 0.5))
+    )));
+
+    // Velvet, all sheen
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(-2.2, 1.0, 0.0),
+        1.0,
+        Arc::new(Sheen::new(Color::new(0.9, 0.2, 0.4), 0.3))
+    )));
+
+    // Cotton, a soft sheen over diffuse
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        Arc::new(Sheen::new_layer(
+            Arc::new(Lambertian::new(Color::new(0.1, 0.2, 0.5))),
+            Color::new(0.8, 0.8, 0.9),
+            0.8
+        ))
+    )));
+
+    // Plain diffuse to compare
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(2.2, 1.0, 0.0),
+        1.0,
+        Arc::new(Lambertian::new(Color::new(0.1, 0.2, 0.5)))
+    )));
+
+    cam.look_from = Point3::new(0.0, 2.0, 8.0);
+    cam.look_at = Point3::new(0.0, 1.0, 0.0);
+    cam.vfov = 40.0;
+    cam.background = COLOR_SKY_BLUE;
+
+    Arc::new(BVH::new(&mut world))
+}
+/// A row of spheres with measured MERL materials, e.g. "./resources/brdfs/gold-metallic-paint.binary".
+/// The front row samples the tabulated distribution, the back row the cosine weighted
+/// hemisphere, to compare their noise. Files that fail to load show up as plain grey diffuse.
+pub fn merl_comparison(cam: &mut Camera, paths: &[&str]) -> Arc<HittableSync> {
+    let mut world = HittableComposite::new();
+
+    world.add_hittable(Arc::new(generate_default_plane(
+        30.0,
+        Some(Color::new(0.5, 0.5, 0.5))
+    )));
+
+    let spacing = 2.2;
+    let start = -spacing * (paths.len() as f64 - 1.0) / 2.0;
+    for (i, path) in paths.iter().enumerate() {
+        for (z, sampling) in [(0.0, MerlSampling::Tabulated), (-spacing, MerlSampling::Cosine)] {
+            let material: Arc<MaterialSync> = match MerlMaterial::load(path, sampling) {
+                Ok(merl) => Arc::new(merl),
+                Err(err) => {
+                    eprintln!("Failed to load MERL BRDF: {err}");
+                    Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+                }
+            };
+
+            world.add_hittable(Arc::new(Sphere::new(
+                Point3::new(start + spacing * i as f64, 1.0, z),
+                1.0,
+                material
+            )));
+        }
+    }
+
+    cam.look_from = Point3::new(0.0, 2.0, 8.0);
+    cam.look_at = Point3::new(0.0, 1.0, 0.0);
+    cam.vfov = 40.0;
+    cam.background = COLOR_SKY_BLUE;
+
+    Arc::new(BVH::new(&mut world))
+}
+
+/// Objects on a shadow catcher ground, with a holdout sphere cutting a hole through them.
+/// Meant to be composited over a photograph, the background is transparent.
+pub fn shadow_catcher_composite(cam: &mut Camera) -> Arc<HittableSync> {
+    let mut world = HittableComposite::new();
+
+    world.add_hittable(Arc::new(Quad::new(
+        Point3::new(-15.0, 0.0, -15.0),
+        Vec3::new(30.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 30.0),
+        // Roughly the color of the photographed ground
+        Arc::new(ShadowCatcher::new(Color::new(0.55, 0.5, 0.45)))
+    )));
+
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(-1.2, 1.0, 0.0),
+        1.0,
+        Arc::new(Lambertian::new(Color::new(0.8, 0.3, 0.1)))
+    )));
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(1.2, 1.0, 0.0),
+        1.0,
+        Arc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.05))
+    )));
+
+    // Stands in for an object of the photograph in front of the rendered ones
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(0.0, 0.5, 2.0),
+        0.5,
+        Arc::new(Holdout)
+    )));
+
+    // Only the light sampled directly casts shadows onto the catcher
+    cam.lights.push(Arc::new(PointLight::new(Point3::new(4.0, 8.0, 5.0), COLOR_WHITE, 1500.0)));
+
+    cam.look_from = Point3::new(0.0, 2.0, 8.0);
+    cam.look_at = Point3::new(0.0, 1.0, 0.0);
+    cam.vfov = 40.0;
+    cam.background = COLOR_SKY_BLUE;
+    cam.transparent_background = true;
+
+    Arc::new(BVH::new(&mut world))
+}
+
+/// Cel shaded objects with outlines, rendered by the toon integrator.
+pub fn toon_shading(cam: &mut Camera) -> Arc<HittableSync> {
+    let mut world = HittableComposite::new();
+    let light_direction = Vec3::new(1.0, 1.5, 1.0);
+
+    world.add_hittable(Arc::new(Quad::new(
+        Point3::new(-15.0, 0.0, -15.0),
+        Vec3::new(30.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 30.0),
+        // No rim, the ground would get one everywhere it is seen at a grazing angle
+        Arc::new(Toon::new_texture(
+            Arc::new(SolidColorTexture::new(Color::new(0.6, 0.8, 0.5))),
+            2,
+            light_direction,
+            COLOR_BLACK,
+            0.0
+        ))
+    )));
+
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(-1.5, 1.0, 0.0),
+        1.0,
+        Arc::new(Toon::new(Color::new(0.9, 0.4, 0.3), 3, light_direction))
+    )));
+
+    let cube = box_new(
+        Point3::new(0.0, 0.0, 0.0),
+        Point3::new(1.4, 1.4, 1.4),
+        Arc::new(Toon::new(Color::new(0.3, 0.5, 0.9), 3, light_direction))
+    );
+    world.add_hittable(Arc::new(Translate::new(
+        Arc::new(RotateY::new(cube, 30.0)),
+        Vec3::new(0.8, 0.0, -0.5)
+    )));
+
+    // Materials without a toon shade are path traced
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(0.3, 0.5, 1.8),
+        0.5,
+        Arc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.0))
+    )));
+
+    cam.look_from = Point3::new(0.0, 3.0, 8.0);
+    cam.look_at = Point3::new(0.0, 0.8, 0.0);
+    cam.vfov = 40.0;
+    cam.background = COLOR_SKY_BLUE;
+    cam.integrator = Integrator::Toon(Outline::default());
+
+    Arc::new(BVH::new(&mut world))
+}
+
+/// A 4x4x3 meter room lit by a warm one-sided ceiling panel and a cool glowing orb.
+/// Lights are set by temperature and power or luminous flux, so scaling the room by s
+/// and the powers by s^2 renders the same image.
+pub fn physical_lights_room(cam: &mut Camera) -> Arc<HittableSync> {
+    let mut world = HittableComposite::new();
+
+    let white = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+    let (width, height, length) = (4.0, 3.0, 4.0);
+    let corner = Point3::new(-width / 2.0, 0.0, -length / 2.0);
+
+    // floor, ceiling and back wall
+    world.add_hittable(Arc::new(Quad::new(corner, Vec3::new(width, 0.0, 0.0), Vec3::new(0.0, 0.0, length), white.clone())));
+    world.add_hittable(Arc::new(Quad::new(
+        corner + Vec3::new(0.0, height, 0.0),
+        Vec3::new(width, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, length),
+        white.clone()
+    )));
+    world.add_hittable(Arc::new(Quad::new(corner, Vec3::new(width, 0.0, 0.0), Vec3::new(0.0, height, 0.0), white.clone())));
+
+    // side walls
+    world.add_hittable(Arc::new(Quad::new(
+        corner,
+        Vec3::new(0.0, 0.0, length),
+        Vec3::new(0.0, height, 0.0),
+        Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)))
+    )));
+    world.add_hittable(Arc::new(Quad::new(
+        corner + Vec3::new(width, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, length),
+        Vec3::new(0.0, height, 0.0),
+        Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)))
+    )));
+
+    // Ceiling panel, its normal (u x v) points down so only the room is lit
+    let panel_u = Vec3::new(0.8, 0.0, 0.0);
+    let panel_v = Vec3::new(0.0, 0.0, 0.8);
+    world.add_hittable(Arc::new(Quad::new(
+        Point3::new(-0.4, height - 0.01, -0.4),
+        panel_u,
+        panel_v,
+        Arc::new(DiffuseLight::new_watts(blackbody(2700.0), 60.0, panel_u.cross(panel_v).length(), true))
+    )));
+
+    // Orb lamp, as bright as 20 W
+    let orb_radius = 0.2;
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(1.0, 0.2, 0.5),
+        orb_radius,
+        Arc::new(DiffuseLight::new_lumens(blackbody(6500.0), 13_660.0, 4.0 * PI * orb_radius * orb_radius, false))
+    )));
+
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(-0.6, 0.6, -0.3),
+        0.6,
+        white
+    )));
+
+    cam.look_from = Point3::new(0.0, 1.5, 6.5);
+    cam.look_at = Point3::new(0.0, 1.3, 0.0);
+    cam.vfov = 45.0;
+    cam.background = COLOR_BLACK;
+
+    Arc::new(BVH::new(&mut world))
+}
+
+/// A wall washed by a row of IES downlights, with a spot light and a projector.
+pub fn ies_and_projector_lights(cam: &mut Camera) -> Arc<HittableSync> {
+    let mut world = HittableComposite::new();
+    let white = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+
+    // floor and wall
+    world.add_hittable(Arc::new(Quad::new(
+        Point3::new(-6.0, 0.0, -2.0),
+        Vec3::new(12.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 10.0),
+        white.clone()
+    )));
+    world.add_hittable(Arc::new(Quad::new(
+        Point3::new(-6.0, 0.0, -2.0),
+        Vec3::new(12.0, 0.0, 0.0),
+        Vec3::new(0.0, 4.0, 0.0),
+        white.clone()
+    )));
+    world.add_hittable(Arc::new(Sphere::new(Point3::new(1.5, 0.5, 1.0), 0.5, white)));
+
+    let warm = blackbody(3000.0);
+    match IesProfile::load("./resources/ies/downlight.ies") {
+        Ok(profile) => {
+            let profile = Arc::new(profile);
+            for x in [-4.0, -1.5, 1.0, 3.5] {
+                cam.lights.push(Arc::new(PointLight::new_ies(
+                    Point3::new(x, 3.8, -1.4),
+                    Vec3::new(0.0, -1.0, 0.0),
+                    warm,
+                    profile.clone(),
+                    1.0
+                )));
+            }
+        }
+        Err(err) => eprintln!("Failed to load IES profile: {err}")
+    }
+
+    cam.lights.push(Arc::new(PointLight::new_spot(
+        Point3::new(3.0, 3.0, 3.0),
+        Point3::new(1.5, 0.5, 1.0) - Point3::new(3.0, 3.0, 3.0),
+        blackbody(5000.0),
+        8.0,
+        10.0,
+        15.0
+    )));
+
+    cam.lights.push(Arc::new(ProjectorLight::new(
+        Point3::new(-2.5, 1.5, 5.0),
+        Point3::new(-2.5, 1.8, -2.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        12.0,
+        2.0,
+        Arc::new(ImageTexture::new("./resources/textures/earthmap.jpg", ColorSpace::Srgb)),
+        30.0
+    )));
+
+    cam.look_from = Point3::new(0.0, 2.0, 9.0);
+    cam.look_at = Point3::new(0.0, 1.5, 0.0);
+    cam.vfov = 45.0;
+    cam.background = COLOR_BLACK;
+
+    Arc::new(BVH::new(&mut world))
+}
+
+/// Hundreds of small glowing orbs over a diffuse ground, in the dark.
+/// Lit mostly through the light tree, each point only samples the orbs that matter to it.
+pub fn many_lights(cam: &mut Camera) -> Arc<HittableSync> {
+    let mut world = HittableComposite::new();
+
+    world.add_hittable(Arc::new(generate_default_plane(60.0, Some(Color::new(0.6, 0.6, 0.6)))));
+
+    for _ in 0..300 {
+        let center = Point3::new(
+            random_f64_in_range(-20.0, 20.0),
+            random_f64_in_range(0.2, 1.5),
+            random_f64_in_range(-20.0, 10.0)
+        );
+        let temperature = random_f64_in_range(1800.0, 9000.0);
+
+        world.add_hittable(Arc::new(Sphere::new(
+            center,
+            0.1,
+            Arc::new(DiffuseLight::new_blackbody(temperature, 40.0))
+        )));
+    }
+
+    for x in [-4.0, 0.0, 4.0] {
+        world.add_hittable(Arc::new(Sphere::new(
+            Point3::new(x, 1.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new(Color::new(0.7, 0.7, 0.7)))
+        )));
+    }
+
+    cam.look_from = Point3::new(0.0, 3.0, 12.0);
+    cam.look_at = Point3::new(0.0, 0.5, 0.0);
+    cam.vfov = 40.0;
+    cam.background = COLOR_BLACK;
+
+    Arc::new(BVH::new(&mut world))
+}
+
+/// A huge image textured ground seen at a grazing angle, the mip pyramid keeps the distance from shimmering.
+pub fn filtered_ground_texture(cam: &mut Camera) -> Arc<HittableSync> {
+    let mut world = HittableComposite::new();
+
+    let earth = ImageTexture::new_filtered(
+        "./resources/textures/earthmap.jpg",
+        ColorSpace::Srgb,
+        FilterMode::Bicubic,
+        WrapMode::Repeat,
+        WrapMode::Mirror
+    );
+    world.add_hittable(Arc::new(Quad::new(
+        Point3::new(-100.0, 0.0, -190.0),
+        Vec3::new(200.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 200.0),
+        Arc::new(Lambertian::new_texture(Arc::new(earth)))
+    )));
+
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        Arc::new(Metal::new(Color::new(0.8, 0.8, 0.8), 0.0))
+    )));
+
+    cam.look_from = Point3::new(0.0, 1.5, 8.0);
+    cam.look_at = Point3::new(0.0, 1.0, 0.0);
+    cam.vfov = 50.0;
+    cam.background = COLOR_SKY_BLUE;
+
+    Arc::new(BVH::new(&mut world))
+}
+
+/// A fine checker floor seen directly and through a mirror and a glass ball.
+/// Ray differentials fade the checks into grey far away and in the
+/// shrunk reflections, instead of aliasing into noise.
+pub fn ray_differentials(cam: &mut Camera) -> Arc<HittableSync> {
+    let mut world = HittableComposite::new();
+
+    let checker = CheckerTexture::new_color(0.25, Color::new(0.1, 0.1, 0.1), Color::new(0.9, 0.9, 0.9));
+    world.add_hittable(Arc::new(Quad::new(
+        Point3::new(-200.0, 0.0, -300.0),
+        Vec3::new(400.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 400.0),
+        Arc::new(Lambertian::new_texture(Arc::new(checker)))
+    )));
+
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(-1.2, 1.0, 0.0),
+        1.0,
+        Arc::new(Metal::new_mirror(Color::new(0.9, 0.9, 0.9)))
+    )));
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(1.2, 1.0, 0.0),
+        1.0,
+        Arc::new(Dielectric::new(1.5))
+    )));
+
+    cam.look_from = Point3::new(0.0, 1.2, 7.0);
+    cam.look_at = Point3::new(0.0, 0.8, 0.0);
+    cam.vfov = 40.0;
+    cam.background = COLOR_SKY_BLUE;
+
+    Arc::new(BVH::new(&mut world))
+}
+
+/// Two rows of spheres showing the procedural patterns, the colored ones through ramps
+pub fn procedural_textures(cam: &mut Camera) -> Arc<HittableSync> {
+    let mut world = HittableComposite::new();
+
+    world.add_hittable(Arc::new(Quad::new(
+        Point3::new(-50.0, 0.0, -50.0),
+        Vec3::new(100.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 100.0),
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    )));
+
+    let terrain = ColorRamp::new(
+        Arc::new(RidgedTexture::new(2.0, 6, 2.0, 2.0, 1.0, 0)),
+        &[
+            (0.45, Color::new(0.1, 0.3, 0.1)),
+            (0.65, Color::new(0.4, 0.35, 0.3)),
+            (0.85, Color::new(0.95, 0.95, 0.95))
+        ]
+    );
+    let veins = ColorRamp::new(
+        Arc::new(MarbleTexture::new(4.0, 6.0, 5, 0)),
+        &[(0.0, Color::new(0.2, 0.25, 0.3)), (0.3, Color::new(0.9, 0.9, 0.88))]
+    );
+    let sunset = ColorRamp::new(
+        Arc::new(GradientTexture::new(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 2.0, 0.0))),
+        &[
+            (0.0, Color::new(0.3, 0.0, 0.4)),
+            (0.5, Color::new(0.9, 0.3, 0.1)),
+            (1.0, Color::new(1.0, 0.9, 0.3))
+        ]
+    );
+    let textures: Vec<Arc<TextureSync>> = vec![
+        Arc::new(FbmTexture::new(3.0, 6, 2.0, 0.5, 0)),
+        Arc::new(WorleyTexture::new(4.0, DistanceMetric::Euclidean, WorleyFeature::F1, 1.0, 1)),
+        Arc::new(WorleyTexture::new(4.0, DistanceMetric::Manhattan, WorleyFeature::F2MinusF1, 1.0, 2)),
+        Arc::new(WorleyTexture::new(4.0, DistanceMetric::Chebyshev, WorleyFeature::F2, 0.8, 3)),
+        Arc::new(terrain),
+        Arc::new(WoodTexture::new(Color::new(0.8, 0.6, 0.4), Color::new(0.4, 0.22, 0.1), 6.0, 1.5, 0)),
+        Arc::new(veins),
+        Arc::new(sunset)
+    ];
+
+    for (index, texture) in textures.into_iter().enumerate() {
+        let (row, column) = (index / 4, index % 4);
+        world.add_hittable(Arc::new(Sphere::new(
+            Point3::new(-3.3 + 2.2 * column as f64, 1.0, -2.2 * row as f64),
+            1.0,
+            Arc::new(Lambertian::new_texture(texture))
+        )));
+    }
+
+    cam.look_from = Point3::new(0.0, 5.0, 9.0);
+    cam.look_at = Point3::new(0.0, 0.5, -1.0);
+    cam.vfov = 45.0;
+    cam.background = COLOR_SKY_BLUE;
+
+    Arc::new(BVH::new(&mut world))
+}
+
+/// Surface looks built as texture graphs from text expressions
+pub fn texture_graph(cam: &mut Camera) -> Arc<HittableSync> {
+    let mut world = HittableComposite::new();
+
+    let looks = [
+        // Rusty tiles, mortar lines from Worley cell borders
+        "multiply(
+            mix(rgb(0.6, 0.3, 0.15), rgb(0.25, 0.12, 0.05), remap(fbm(6, 5, 2, 0.5), 0.35, 0.65, 0, 1)),
+            remap(worley(3, chebyshev, f2_minus_f1, 0.3), 0, 0.08, 0.2, 1)
+        )",
+        // The earth with its hue turned and mostly desaturated, spun halfway around
+        "hsv(uv_transform(image(\"./resources/textures/earthmap.jpg\", srgb), 1, 1, 0.5, 0, 0), 0.4, 0.5, 1)",
+        // Stretched, tilted marble
+        "ramp(
+            transform(marble(3, 8, 5), vec(0, 0, 0), vec(1, 3, 1), 35),
+            0, rgb(0.05, 0.05, 0.05), 0.3, rgb(0.3, 0.5, 0.35), 1, rgb(0.9, 0.95, 0.9)
+        )",
+        // Inverted checker under a noise veil
+        "add(multiply(invert(checker(0.3, rgb(1, 1, 1), rgb(0.2, 0.4, 0.8))), 0.8), multiply(noise(4), 0.2))"
+    ];
+
+    for (index, look) in looks.iter().enumerate() {
+        let texture = parse_texture(look).unwrap_or_else(|error| panic!("invalid texture expression: {error}"));
+        world.add_hittable(Arc::new(Sphere::new(
+            Point3::new(-3.3 + 2.2 * index as f64, 1.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new_texture(texture))
+        )));
+    }
+
+    // A neon grid behind them, glowing with a texture graph too
+    let neon = parse_texture("multiply(uv_grid(10, 3, 0.06, rgb(1, 0.5, 0.15), rgb(0, 0, 0)), 6)")
+        .unwrap_or_else(|error| panic!("invalid texture expression: {error}"));
+    world.add_hittable(Arc::new(Quad::new(
+        Point3::new(-5.0, 0.2, -3.0),
+        Vec3::new(10.0, 0.0, 0.0),
+        Vec3::new(0.0, 3.0, 0.0),
+        Arc::new(DiffuseLight::new(neon))
+    )));
+
+    world.add_hittable(Arc::new(Quad::new(
+        Point3::new(-50.0, 0.0, -50.0),
+        Vec3::new(100.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 100.0),
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    )));
+
+    cam.look_from = Point3::new(0.0, 2.5, 9.0);
+    cam.look_at = Point3::new(0.0, 1.0, 0.0);
+    cam.vfov = 40.0;
+    cam.background = COLOR_SKY_BLUE;
+
+    Arc::new(BVH::new(&mut world))
+}
+
+/// Patterns that follow the surface: UV checkers and grids, and triplanar projections
+/// for surfaces whose texture coordinates stretch, like the poles of a sphere.
+pub fn uv_and_triplanar(cam: &mut Camera) -> Arc<HittableSync> {
+    let mut world = HittableComposite::new();
+
+    let floor_checks = UvCheckerTexture::new_color(40.0, 40.0, Color::new(0.3, 0.3, 0.3), Color::new(0.6, 0.6, 0.6));
+    world.add_hittable(Arc::new(Quad::new(
+        Point3::new(-20.0, 0.0, -20.0),
+        Vec3::new(40.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 40.0),
+        Arc::new(Lambertian::new_texture(Arc::new(floor_checks)))
+    )));
+
+    let grid: Arc<TextureSync> = Arc::new(UvGridTexture::new(
+        4.0, 4.0, 0.08, Color::new(0.05, 0.05, 0.05), Color::new(0.9, 0.6, 0.2)
+    ));
+    let earth: Arc<TextureSync> = Arc::new(ImageTexture::new_filtered(
+        "./resources/textures/earthmap.jpg",
+        ColorSpace::Srgb,
+        FilterMode::Bilinear,
+        WrapMode::Repeat,
+        WrapMode::Repeat
+    ));
+
+    let textures: [Arc<TextureSync>; 4] = [
+        Arc::new(UvCheckerTexture::new_color(16.0, 8.0, Color::new(0.8, 0.1, 0.1), Color::new(0.9, 0.9, 0.9))),
+        Arc::new(UvGridTexture::new(16.0, 8.0, 0.06, Color::new(0.05, 0.05, 0.05), Color::new(0.2, 0.5, 0.9))),
+        // Same grid, projected, so it doesn't pinch at the poles
+        Arc::new(TriplanarTexture::new(grid, 1.0, 4.0)),
+        Arc::new(TriplanarTexture::new(earth.clone(), 0.5, 8.0))
+    ];
+    for (index, texture) in textures.into_iter().enumerate() {
+        world.add_hittable(Arc::new(Sphere::new(
+            Point3::new(-3.3 + 2.2 * index as f64, 1.0, 0.0),
+            1.0,
+            Arc::new(Lambertian::new_texture(texture))
+        )));
+    }
+
+    let projected_box = box_new(
+        Point3::new(-0.75, 0.0, -0.75),
+        Point3::new(0.75, 1.5, 0.75),
+        Arc::new(Lambertian::new_texture(Arc::new(TriplanarTexture::new(earth, 0.4, 8.0))))
+    );
+    world.add_hittable(Arc::new(Translate::new(
+        Arc::new(RotateY::new(projected_box, 30.0)),
+        Vec3::new(0.0, 0.0, -3.0)
+    )));
+
+    cam.look_from = Point3::new(0.0, 3.5, 8.0);
+    cam.look_at = Point3::new(0.0, 0.8, -0.5);
+    cam.vfov = 45.0;
+    cam.background = COLOR_SKY_BLUE;
+
+    Arc::new(BVH::new(&mut world))
+}
+
+/// Fixed seed fBm and marble over improved Perlin noise on the left and simplex noise
+/// on the right, the back row animated along the fourth dimension.
+/// Render with increasing time for a smoothly evolving sequence.
+pub fn noise_bases(cam: &mut Camera, time: f64) -> Arc<HittableSync> {
+    let mut world = HittableComposite::new();
+
+    world.add_hittable(Arc::new(Quad::new(
+        Point3::new(-50.0, 0.0, -50.0),
+        Vec3::new(100.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 100.0),
+        Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)))
+    )));
+
+    let veins = [(0.0, Color::new(0.1, 0.1, 0.15)), (0.4, Color::new(0.85, 0.85, 0.8))];
+    for (column, basis) in [NoiseBasis::Perlin, NoiseBasis::Simplex].into_iter().enumerate() {
+        for (row, animated) in [false, true].into_iter().enumerate() {
+            let source = NoiseSource::new(7, basis);
+            let source = if animated { source.at_time(time) } else { source };
+
+            let mut fbm = FbmTexture::new(3.0, 6, 2.0, 0.5, 7);
+            fbm.noise = source;
+            let mut marble = MarbleTexture::new(3.0, 6.0, 5, 7);
+            marble.noise = source;
+            let marble = ColorRamp::new(Arc::new(marble), &veins);
+
+            let z = -2.5 * row as f64;
+            let x = -3.3 + 4.4 * column as f64;
+            world.add_hittable(Arc::new(Sphere::new(
+                Point3::new(x, 1.0, z),
+                1.0,
+                Arc::new(Lambertian::new_texture(Arc::new(fbm)))
+            )));
+            world.add_hittable(Arc::new(Sphere::new(
+                Point3::new(x + 2.2, 1.0, z),
+                1.0,
+                Arc::new(Lambertian::new_texture(Arc::new(marble)))
+            )));
+        }
+    }
+
+    cam.look_from = Point3::new(0.0, 4.5, 9.0);
+    cam.look_at = Point3::new(0.0, 0.5, -1.0);
+    cam.vfov = 45.0;
+    cam.background = COLOR_SKY_BLUE;
+
+    Arc::new(BVH::new(&mut world))
+}
+
+/// A sphere and a box on a floor, under a panel light and the sky.
+/// Also returns the floor, to bake its lighting into a texture.
+pub fn lightmap_floor(cam: &mut Camera) -> (Arc<HittableSync>, Arc<UvSurfaceSync>) {
+    let mut world = HittableComposite::new();
+
+    // Baking lights the side the normal (u x v) points to, so the floor faces up
+    let floor = Arc::new(Quad::new(
+        Point3::new(-3.0, 0.0, 3.0),
+        Vec3::new(6.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, -6.0),
+        Arc::new(Lambertian::new_texture(Arc::new(UvCheckerTexture::new_color(
+            8.0,
+            8.0,
+            Color::new(0.8, 0.8, 0.8),
+            Color::new(0.55, 0.6, 0.7)
+        ))))
+    ));
+    world.add_hittable(floor.clone());
+
+    world.add_hittable(Arc::new(Sphere::new(
+        Point3::new(-1.0, 1.0, 0.0),
+        1.0,
+        Arc::new(Lambertian::new(Color::new(0.8, 0.3, 0.2)))
+    )));
+    let cube = box_new(
+        Point3::new(0.0, 0.0, 0.0),
+        Point3::new(1.2, 1.2, 1.2),
+        Arc::new(Lambertian::new(Color::new(0.2, 0.5, 0.3)))
+    );
+    world.add_hittable(Arc::new(Translate::new(Arc::new(RotateY::new(cube, 25.0)), Vec3::new(0.8, 0.0, -0.6))));
+
+    // Facing down
+    let panel_u = Vec3::new(1.5, 0.0, 0.0);
+    let panel_v = Vec3::new(0.0, 0.0, 1.5);
+    world.add_hittable(Arc::new(Quad::new(
+        Point3::new(-1.5, 4.0, -1.0),
+        panel_u,
+        panel_v,
+        Arc::new(DiffuseLight::new_watts(blackbody(4500.0), 150.0, panel_u.cross(panel_v).length(), true))
+    )));
+
+    cam.look_from = Point3::new(0.0, 5.0, 8.0);
+    cam.look_at = Point3::new(0.0, 0.5, 0.0);
+    cam.vfov = 40.0;
+    cam.background = COLOR_SKY_BLUE * 0.4;
+
+    (Arc::new(BVH::new(&mut world)), floor)
+}
+
+/// Ridged terrain, a rock grown from an octahedron and a dented sphere, all displaced
+/// for real rather than bump mapped.
+pub fn displaced_terrain(cam: &mut Camera, tessellation: Tessellation) -> Arc<HittableSync> {
+    let mut world = HittableComposite::new();
+
+    // Grass in the valleys, rock on the slopes and snow on the peaks
+    let terrain_colors = ColorRamp::new(
+        Arc::new(GradientTexture::new(Point3::new(0.0, 0.6, 0.0), Point3::new(0.0, 1.2, 0.0))),
+        &[
+            (0.2, Color::new(0.15, 0.35, 0.1)),
+            (0.5, Color::new(0.4, 0.35, 0.3)),
+            (0.85, Color::new(0.9, 0.9, 0.95))
+        ]
+    );
+    // Faces up, u x v points along +Y
+    let terrain = Quad::new(
+        Point3::new(-6.0, 0.0, 4.0),
+        Vec3::new(12.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, -12.0),
+        Arc::new(Lambertian::new_texture(Arc::new(terrain_colors)))
+    );
+    let ridges = Displacement::new(Arc::new(RidgedTexture::new(0.35, 6, 2.0, 2.0, 1.0, 0)), 1.2, tessellation);
+    world.add_hittable(ridges.displace_surface(&terrain));
+
+    // Octahedron with normals pointing away from its center, so the displaced mesh stays closed
+    let rock_center = Point3::new(-1.2, 1.9, 2.0);
+    let corner = |direction: Vec3| Vertex::new(rock_center + 0.4 * direction, direction, 0.0, 0.0);
+    let axes = [Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0), Vec3::new(-1.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0)];
+    let rock_material: Arc<MaterialSync> = Arc::new(Lambertian::new(Color::new(0.45, 0.4, 0.38)));
+    let mut octahedron = Vec::new();
+    for side in 0..4 {
+        let (first, second) = (axes[side], axes[(side + 1) % 4]);
+        octahedron.push(Triangle::new([corner(first), corner(second), corner(Vec3::new(0.0, 1.0, 0.0))], rock_material.clone()));
+        octahedron.push(Triangle::new([corner(second), corner(first), corner(Vec3::new(0.0, -1.0, 0.0))], rock_material.clone()));
+    }
+    let mut lumps = FbmTexture::new(2.5, 5, 2.0, 0.5, 3);
+    lumps.noise = NoiseSource::new(3, NoiseBasis::Simplex);
+    let lumpy = Displacement::new(Arc::new(lumps), 1.0, tessellation);
+    world.add_hittable(lumpy.displace_mesh(&octahedron));
+
+    // Worley cells pushed into a metal sphere
+    let ball = Sphere::new(
+        Point3::new(1.4, 2.0, 1.5),
+        0.7,
+        Arc::new(Metal::new(Color::new(0.8, 0.7, 0.5), 0.2))
+    );
+    let dents = Displacement::new(
+        Arc::new(WorleyTexture::new(4.0, DistanceMetric::Euclidean, WorleyFeature::F1, 1.0, 7)),
+        -0.1,
+        tessellation
+    );
+    world.add_hittable(dents.displace_surface(&ball));
+
+    cam.look_from = Point3::new(0.0, 4.5, 9.0);
+    cam.look_at = Point3::new(0.0, 1.2, 0.0);
+    cam.vfov = 40.0;
+    cam.background = COLOR_SKY_BLUE;
+
+    Arc::new(BVH::new(&mut world))
+}